@@ -0,0 +1,40 @@
+#![feature(test)]
+
+extern crate test;
+use gmorph::batch::EncBatch;
+use gmorph::*;
+use test::Bencher;
+
+#[bench]
+fn bench_batch_mul(b: &mut Bencher) {
+    let key_pair = KeyPair::new();
+    let values: Vec<_> = (1..1000).collect();
+    let xs = EncBatch::encrypt(&key_pair, &values);
+
+    b.iter(|| test::black_box(&xs * &xs))
+}
+
+#[bench]
+fn bench_elementwise_mul(b: &mut Bencher) {
+    let key_pair = KeyPair::new();
+    let xs: Vec<_> = (1..1000).map(|x| Enc::encrypt(&key_pair, x)).collect();
+
+    b.iter(|| test::black_box(xs.iter().map(|&x| x * x).collect::<Vec<_>>()))
+}
+
+#[bench]
+fn bench_batch_decrypt(b: &mut Bencher) {
+    let key_pair = KeyPair::new();
+    let values: Vec<_> = (1..1000).collect();
+    let xs = EncBatch::encrypt(&key_pair, &values);
+
+    b.iter(|| test::black_box(xs.decrypt(&key_pair)))
+}
+
+#[bench]
+fn bench_elementwise_decrypt(b: &mut Bencher) {
+    let key_pair = KeyPair::new();
+    let xs: Vec<_> = (1..1000).map(|x| Enc::encrypt(&key_pair, x)).collect();
+
+    b.iter(|| test::black_box(xs.iter().map(|x| x.decrypt(&key_pair)).collect::<Vec<_>>()))
+}
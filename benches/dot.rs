@@ -0,0 +1,22 @@
+#![feature(test)]
+
+extern crate test;
+use gmorph::*;
+use test::Bencher;
+
+#[bench]
+fn bench_dot(b: &mut Bencher) {
+    let key_pair = KeyPair::new();
+    let xs: Vec<_> = (1..1000).map(|x| Enc::encrypt(&key_pair, x)).collect();
+
+    b.iter(|| test::black_box(Enc::dot(&xs, &xs)))
+}
+
+#[bench]
+fn bench_fold(b: &mut Bencher) {
+    let key_pair = KeyPair::new();
+    let xs: Vec<_> = (1..1000).map(|x| Enc::encrypt(&key_pair, x)).collect();
+    let zero = Enc::encrypt(&key_pair, 0);
+
+    b.iter(|| test::black_box(xs.iter().fold(zero, |acc, &x| acc + x * x)))
+}
@@ -0,0 +1,18 @@
+#![feature(test)]
+
+extern crate test;
+use gmorph::*;
+use test::Bencher;
+
+#[bench]
+fn bench_encrypt(b: &mut Bencher) {
+    let key_pair = KeyPair::new();
+
+    b.iter(|| {
+        test::black_box(
+            (1..1000)
+                .map(|x| Enc::encrypt(&key_pair, x))
+                .collect::<Vec<_>>(),
+        )
+    })
+}
@@ -0,0 +1,41 @@
+#![feature(test)]
+
+extern crate test;
+use gmorph::wire::EncArray;
+use gmorph::*;
+use test::Bencher;
+
+fn encoded(n: u32) -> (Enc, Vec<Enc>, Vec<u8>) {
+    let key_pair = KeyPair::new();
+    let zero = Enc::encrypt(&key_pair, 0);
+    let xs: Vec<_> = (0..n).map(|x| Enc::encrypt(&key_pair, x)).collect();
+    let bytes = xs.iter().flat_map(|x| x.to_bytes().to_vec()).collect();
+    (zero, xs, bytes)
+}
+
+#[bench]
+fn bench_array_sum(b: &mut Bencher) {
+    let (_, _, bytes) = encoded(1000);
+    let xs = EncArray::new(&bytes).unwrap();
+
+    b.iter(|| test::black_box(xs.sum().unwrap()))
+}
+
+#[bench]
+fn bench_decoded_sum(b: &mut Bencher) {
+    let (zero, _, bytes) = encoded(1000);
+    let xs = EncArray::new(&bytes).unwrap();
+
+    b.iter(|| test::black_box(xs.iter().fold(zero, |acc, x| acc + x.unwrap())))
+}
+
+#[bench]
+fn bench_json_sum(b: &mut Bencher) {
+    let (zero, xs, _) = encoded(1000);
+    let json = serde_json::to_string(&xs).unwrap();
+
+    b.iter(|| {
+        let xs: Vec<Enc> = serde_json::from_str(&json).unwrap();
+        test::black_box(xs.into_iter().fold(zero, |acc, x| acc + x))
+    })
+}
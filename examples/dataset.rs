@@ -0,0 +1,38 @@
+use gmorph::dataset::{DatasetReader, DatasetWriter};
+use gmorph::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+fn main() -> Result<(), Error> {
+    let key_pair = KeyPair::new();
+
+    let file = BufWriter::new(File::create("enc.gmds")?);
+    let mut writer = DatasetWriter::new(file, key_pair.fingerprint(), &["x", "x^2"])?;
+    for x in 1..10 {
+        let row = [Enc::encrypt(&key_pair, x), Enc::encrypt(&key_pair, x * x)];
+        writer.write_row(&row)?;
+    }
+    writer.finish()?;
+
+    let file = BufReader::new(File::open("enc.gmds")?);
+    let mut reader = DatasetReader::new(file)?;
+    reader.check_key(&key_pair)?;
+    println!("columns: {:?}", reader.header().columns());
+
+    // the square of 5 sits in the second column of the fifth row
+    assert_eq!(25, reader.get(9)?.try_decrypt(&key_pair)?);
+
+    let mut reader = DatasetReader::new(File::open("enc.gmds")?)?;
+    let enc_sum = reader
+        .by_ref()
+        .step_by(2)
+        .try_fold(Enc::encrypt(&key_pair, 0), |acc, x| x.map(|x| acc + x))?;
+
+    let given = enc_sum.try_decrypt(&key_pair)?;
+    let expected: u32 = (1..10).sum();
+
+    assert_eq!(expected, given, "the sums should be equal, and equal to 45");
+
+    println!("{}", given);
+    Ok(())
+}
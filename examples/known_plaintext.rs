@@ -0,0 +1,22 @@
+use gmorph::cryptanalysis::{linear_key_recovery, trace_invariant};
+use gmorph::*;
+
+fn main() {
+    let key_pair = KeyPair::new();
+
+    let report = linear_key_recovery(&key_pair, 1000, 10, 100);
+    println!(
+        "linear key recovery: {} pairs used, rank {}, {:.0}% of fresh ciphertexts decrypted",
+        report.pairs_used,
+        report.rank,
+        report.success_rate * 100.0
+    );
+
+    let report = trace_invariant(&key_pair, 100);
+    println!(
+        "trace invariant: holds = {}, plaintext leaked in {:.0}% of {} samples",
+        report.invariant_holds,
+        report.success_rate * 100.0,
+        report.samples
+    );
+}
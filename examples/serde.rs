@@ -1,22 +1,24 @@
 use gmorph::*;
+use std::error::Error;
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
     let key_pair = KeyPair::new();
     let enc: Vec<_> = (1..10).map(|x| Enc::encrypt(&key_pair, x)).collect();
 
-    let serialized = serde_json::to_string(&enc).unwrap();
-    let serialized_keypair = serde_json::to_string(&key_pair).unwrap();
+    let serialized = serde_json::to_string(&enc)?;
+    let serialized_keypair = serde_json::to_string(&key_pair)?;
 
-    let deserialized: Vec<Enc> = serde_json::from_str(&serialized).unwrap();
+    let deserialized: Vec<Enc> = serde_json::from_str(&serialized)?;
     let enc_sum = deserialized
         .into_iter()
         .fold(Enc::encrypt(&key_pair, 0), |acc, x| acc + x);
 
-    let key_pair: KeyPair = serde_json::from_str(&serialized_keypair).unwrap();
-    let given = enc_sum.decrypt(&key_pair);
+    let key_pair: KeyPair = serde_json::from_str(&serialized_keypair)?;
+    let given = enc_sum.try_decrypt(&key_pair)?;
     let expected: u32 = (1..10).sum();
 
     assert_eq!(expected, given, "the sums should be equal, and equal to 45");
 
     println!("{}", given);
+    Ok(())
 }
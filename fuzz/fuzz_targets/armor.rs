@@ -0,0 +1,24 @@
+//! Parses ciphertexts and keys from untrusted armored text
+//!
+//! Anything accepted has to display as the same text, up to surrounding
+//! whitespace, since the encoding is canonical.
+#![no_main]
+use gmorph::{Enc, KeyPair};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    if let Ok(enc) = text.parse::<Enc>() {
+        assert_eq!(enc.to_string(), text.trim());
+    }
+    if let Ok(key_pair) = text.parse::<KeyPair>() {
+        assert_eq!(key_pair.to_string(), text.trim());
+    }
+    // errors point into the text
+    if let Err(e) = text.parse::<Enc>() {
+        let _ = e.to_string();
+    }
+});
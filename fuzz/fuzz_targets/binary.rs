@@ -0,0 +1,50 @@
+//! Decodes ciphertexts, keys, arrays and datasets from untrusted bytes
+//!
+//! The binary encoding is canonical, so anything accepted has to encode
+//! back to the very same bytes.
+#![no_main]
+use gmorph::dataset::{DatasetReader, DatasetView};
+use gmorph::wire::EncArray;
+use gmorph::{Enc, KeyPair};
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(enc) = Enc::from_bytes(data) {
+        assert_eq!(enc.to_bytes()[..], data[..]);
+    }
+    if let Ok(key_pair) = KeyPair::from_bytes(data) {
+        assert_eq!(key_pair.to_bytes()[..], data[..]);
+    }
+
+    if let Ok(array) = EncArray::new(data) {
+        let decoded: Result<Vec<Enc>, _> = array.iter().collect();
+        match (decoded, array.sum()) {
+            (Ok(encs), Ok(sum)) => {
+                let expected = encs.into_iter().fold(None, |acc: Option<Enc>, x| {
+                    Some(acc.map_or(x, |acc| acc + x))
+                });
+                if let Some(expected) = expected {
+                    assert_eq!(sum, expected);
+                }
+            }
+            (Err(_), Err(_)) => {}
+            (decoded, sum) => panic!(
+                "decoding and summing disagree: {:?} vs {:?}",
+                decoded.is_ok(),
+                sum.is_ok()
+            ),
+        }
+    }
+
+    if let Ok(view) = DatasetView::new(data) {
+        for record in view.records().iter() {
+            let _ = record;
+        }
+    }
+    if let Ok(reader) = DatasetReader::new(Cursor::new(data)) {
+        for record in reader {
+            let _ = record;
+        }
+    }
+});
@@ -0,0 +1,34 @@
+//! Checks the modular reductions and field arithmetic against naive `%`
+//!
+//! Run with `--features experimental-normalize` to check multiplication
+//! with the experimental reduction.
+#![no_main]
+use gmorph::fuzzing::{self, MODULUS};
+use libfuzzer_sys::fuzz_target;
+
+const P: u128 = MODULUS as u128;
+
+fuzz_target!(|input: (u64, u64, u32, u32)| {
+    let (hi, lo, a, b) = input;
+
+    assert_eq!(u128::from(fuzzing::normalize_u64(lo)), u128::from(lo) % P);
+    let wide = u128::from(hi) << 64 | u128::from(lo);
+    assert_eq!(u128::from(fuzzing::normalize_u128(wide)), wide % P);
+
+    let (x, y) = (u128::from(a) % P, u128::from(b) % P);
+    let product = (x * y) as u64;
+    assert_eq!(
+        u128::from(fuzzing::normalize_product(product)),
+        u128::from(product) % P,
+        "normalize_product({})",
+        product
+    );
+
+    assert_eq!(u128::from(fuzzing::add(a, b)), (x + y) % P);
+    assert_eq!(u128::from(fuzzing::sub(a, b)), (x + P - y) % P);
+    assert_eq!(u128::from(fuzzing::mul(a, b)), x * y % P);
+    match fuzzing::div(a, b) {
+        Some(q) => assert_eq!(u128::from(q) * y % P, x),
+        None => assert_eq!(y, 0),
+    }
+});
@@ -0,0 +1,23 @@
+//! Deserializes ciphertexts and keys from untrusted CBOR
+//!
+//! Anything accepted has to serialize back to a value which deserializes
+//! to the same thing.
+#![no_main]
+use gmorph::{Enc, KeyPair};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(enc) = serde_cbor::from_slice::<Enc>(data) {
+        let json = serde_cbor::to_vec(&enc).unwrap();
+        assert_eq!(serde_cbor::from_slice::<Enc>(&json).unwrap(), enc);
+    }
+    if let Ok(encs) = serde_cbor::from_slice::<Vec<Enc>>(data) {
+        let json = serde_cbor::to_vec(&encs).unwrap();
+        assert_eq!(serde_cbor::from_slice::<Vec<Enc>>(&json).unwrap(), encs);
+    }
+    if let Ok(key_pair) = serde_cbor::from_slice::<KeyPair>(data) {
+        let json = serde_cbor::to_vec(&key_pair).unwrap();
+        let copy: KeyPair = serde_cbor::from_slice(&json).unwrap();
+        assert_eq!(copy.to_bytes()[..], key_pair.to_bytes()[..]);
+    }
+});
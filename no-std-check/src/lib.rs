@@ -0,0 +1,63 @@
+//! Checks that `gmorph` builds and works without the standard library
+//!
+//! Building for a target without `std` fails if anything `gmorph` needs
+//! pulls the standard library in:
+//!
+//! ```text
+//! cargo build -p no-std-check --target thumbv7m-none-eabi
+//! ```
+#![cfg_attr(not(test), no_std)]
+
+use gmorph::{Decrypt, Enc, KeyPair};
+use rand::{Error, RngCore};
+
+/// SplitMix64, standing in for whatever RNG the device provides
+pub struct SplitMix64(pub u64);
+
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Encrypts `x` and `y` under a fresh key, and decrypts `x * y + x`
+pub fn mul_add(seed: u64, x: u32, y: u32) -> u32 {
+    let mut rng = SplitMix64(seed);
+    let key_pair = KeyPair::generate(&mut rng);
+    let enc_x = Enc::encrypt_with_rng(&key_pair, x, &mut rng);
+    let enc_y = Enc::encrypt_with_rng(&key_pair, y, &mut rng);
+    (enc_x * enc_y + enc_x).decrypt(&key_pair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_add_is_homomorphic() {
+        for seed in 0..10 {
+            assert_eq!(mul_add(seed, 6, 7), 48);
+            assert_eq!(mul_add(seed, 1 << 16, 1 << 14), (1 << 30) + (1 << 16));
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Complex numbers over GF(2^31 - 1)
+//!
+//! Since 2^31 - 1 = 3 (mod 4), -1 is not a square in GF(2^31 - 1), so
+//! adjoining `i` with `i^2 = -1` gives the field GF(p^2) = GF(p)[i]. It
+//! sits inside the quaternions as the span of `1` and `i`, which is how
+//! complex numbers are encrypted.
+use super::{m231::Mod231, q231::Q231, quaternion::QuaternionM, Invertible};
+use crate::error::Error;
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use num_traits::{One, Zero};
+#[cfg(any(test, feature = "testkit"))]
+use quickcheck::{Arbitrary, Gen};
+use rand::distributions::{Distribution, Standard};
+use rand::Rng;
+
+/// Complex number `re + im * i` with coordinates in GF(2^31 - 1), i.e.
+/// a Gaussian integer modulo 2^31 - 1
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct C231 {
+    re: Mod231,
+    im: Mod231,
+}
+
+impl C231 {
+    /// Creates the complex number `re + im * i`
+    #[inline]
+    pub fn new(re: Mod231, im: Mod231) -> Self {
+        Self { re, im }
+    }
+
+    /// Real part
+    #[inline]
+    pub fn re(&self) -> Mod231 {
+        self.re
+    }
+
+    /// Imaginary part
+    #[inline]
+    pub fn im(&self) -> Mod231 {
+        self.im
+    }
+
+    /// Complex conjugate `re - im * i`, which is also the Frobenius map
+    #[inline]
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// Norm `re^2 + im^2`, which is zero only for zero
+    #[inline]
+    pub fn norm2(&self) -> Mod231 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Raises to the power `exp`
+    #[inline]
+    pub fn pow(&self, exp: u64) -> Self {
+        super::pow(*self, exp)
+    }
+
+    /// Division, failing instead of panicking if `other` is zero
+    #[inline]
+    pub fn try_div(&self, other: &Self) -> Result<Self, Error> {
+        other
+            .try_invert()
+            .map(|inverse| *self * inverse)
+            .ok_or(Error::NotInvertible)
+    }
+}
+
+impl fmt::Debug for C231 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}+{:?}i", self.re, self.im)
+    }
+}
+
+impl fmt::Display for C231 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl From<Mod231> for C231 {
+    #[inline]
+    fn from(re: Mod231) -> Self {
+        Self::new(re, Mod231::zero())
+    }
+}
+
+impl From<C231> for (Mod231, Mod231) {
+    #[inline]
+    fn from(z: C231) -> Self {
+        (z.re, z.im)
+    }
+}
+
+impl From<C231> for Q231 {
+    /// The quaternion `re + im * i`
+    #[inline]
+    fn from(z: C231) -> Self {
+        Q231::new(z.re, z.im, Mod231::zero(), Mod231::zero())
+    }
+}
+
+impl TryFrom<Q231> for C231 {
+    type Error = Error;
+
+    /// Fails with `Error::NotComplex` unless the `j` and `k` coordinates
+    /// are zero
+    fn try_from(q: Q231) -> Result<Self, Self::Error> {
+        let q = QuaternionM::from(q);
+        if q.j.is_zero() && q.k.is_zero() {
+            Ok(Self::new(q.w, q.i))
+        } else {
+            Err(Error::NotComplex)
+        }
+    }
+}
+
+impl Invertible for C231 {
+    type Item = C231;
+
+    /// The conjugate divided by the norm
+    fn try_invert(&self) -> Option<Self::Item> {
+        let renorm = self.norm2().try_invert()?;
+        let conjugate = self.conjugate();
+        Some(Self::new(conjugate.re * renorm, conjugate.im * renorm))
+    }
+}
+
+impl Distribution<C231> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> C231 {
+        C231::new(rng.gen(), rng.gen())
+    }
+}
+
+impl Add for C231 {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl AddAssign for C231 {
+    #[inline]
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other
+    }
+}
+
+impl Sub for C231 {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl SubAssign for C231 {
+    #[inline]
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other
+    }
+}
+
+impl Neg for C231 {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl Mul for C231 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl MulAssign for C231 {
+    #[inline]
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other
+    }
+}
+
+impl Div for C231 {
+    type Output = Self;
+
+    /// Panics if `other` is zero, see `try_div`
+    #[inline]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, other: Self) -> Self {
+        self * other.invert()
+    }
+}
+
+impl Zero for C231 {
+    #[inline]
+    fn zero() -> Self {
+        Self::from(Mod231::zero())
+    }
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        self.re.is_zero() && self.im.is_zero()
+    }
+}
+
+impl One for C231 {
+    #[inline]
+    fn one() -> Self {
+        Self::from(Mod231::one())
+    }
+}
+
+#[cfg(any(test, feature = "testkit"))]
+impl Arbitrary for C231 {
+    fn arbitrary<G: Gen>(g: &mut G) -> C231 {
+        C231::new(Mod231::arbitrary(g), Mod231::arbitrary(g))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let (re, im) = (self.re, self.im);
+        let iter = re.shrink().map(move |re| C231::new(re, im));
+        Box::new(iter.chain(im.shrink().map(move |im| C231::new(re, im))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::TestResult;
+
+    #[quickcheck]
+    fn prop_embedding_homomorphic(a: C231, b: C231) -> bool {
+        Q231::from(a * b) == Q231::from(a) * Q231::from(b)
+            && Q231::from(a + b) == Q231::from(a) + Q231::from(b)
+            && C231::try_from(Q231::from(a)).ok() == Some(a)
+    }
+
+    #[quickcheck]
+    fn prop_mul_commutative(a: C231, b: C231) -> bool {
+        a * b == b * a
+    }
+
+    #[quickcheck]
+    fn prop_field(a: C231, b: C231) -> TestResult {
+        if a.is_zero() {
+            return TestResult::from_bool(a.try_invert().is_none() && b.try_div(&a).is_err());
+        }
+        TestResult::from_bool(
+            a * a.invert() == C231::one()
+                && (b / a) * a == b
+                && (a * b).norm2() == a.norm2() * b.norm2(),
+        )
+    }
+
+    #[quickcheck]
+    fn prop_pow_matches_frobenius(a: C231) -> bool {
+        // raising to the p-th power is the Frobenius map, i.e. conjugation
+        a.pow(u64::from(crate::algebra::MODULUS)) == a.conjugate()
+    }
+
+    #[test]
+    fn i_squared_is_minus_one() {
+        let i = C231::new(Mod231::zero(), Mod231::one());
+        assert_eq!(i * i, -C231::one());
+        let j = Q231::new(Mod231(0), Mod231(0), Mod231(1), Mod231(0));
+        assert!(matches!(C231::try_from(j), Err(Error::NotComplex)));
+    }
+}
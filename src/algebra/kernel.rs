@@ -0,0 +1,141 @@
+//! Multiplication kernels for 3x3 matrices of `Q231`
+//!
+//! With `Q231` stored as a 2x2 matrix over GF(2^31 - 1), a 3x3 matrix of
+//! `Q231` is a 6x6 matrix over the field. The kernels multiply in that
+//! form, accumulating the unreduced 62-bit products of each inner sum in a
+//! `u128`, and reduce only once per output coefficient instead of after
+//! every multiplication and addition.
+use super::m231::{normalize_u128, Mod231};
+use super::{Matrix3, Q231};
+use core::borrow::Borrow;
+
+type Flat = [[u64; 6]; 6];
+type Accumulator = [[u128; 6]; 6];
+
+/// Views a 3x3 matrix of `Q231` as a 6x6 matrix over the field
+#[inline]
+pub(crate) fn split(m: &Matrix3<Q231>) -> [[Mod231; 6]; 6] {
+    let mut out = [[Mod231(0); 6]; 6];
+    for r in 0..3 {
+        for c in 0..3 {
+            let [m00, m01, m10, m11] = m[(r, c)].entries();
+            out[2 * r][2 * c] = m00;
+            out[2 * r][2 * c + 1] = m01;
+            out[2 * r + 1][2 * c] = m10;
+            out[2 * r + 1][2 * c + 1] = m11;
+        }
+    }
+    out
+}
+
+/// Inverse of `split`
+#[inline]
+pub(crate) fn join(m: &[[Mod231; 6]; 6]) -> Matrix3<Q231> {
+    Matrix3::from_fn(|r, c| {
+        Q231::from_entries([
+            m[2 * r][2 * c],
+            m[2 * r][2 * c + 1],
+            m[2 * r + 1][2 * c],
+            m[2 * r + 1][2 * c + 1],
+        ])
+    })
+}
+
+#[inline]
+fn flatten(m: &Matrix3<Q231>) -> Flat {
+    let mut out = [[0; 6]; 6];
+    for (out_row, row) in out.iter_mut().zip(split(m).iter()) {
+        for (o, x) in out_row.iter_mut().zip(row.iter()) {
+            *o = x.0.into();
+        }
+    }
+    out
+}
+
+#[inline]
+fn accumulate(acc: &mut Accumulator, a: &Flat, b: &Flat) {
+    for (acc_row, a_row) in acc.iter_mut().zip(a.iter()) {
+        for (j, acc) in acc_row.iter_mut().enumerate() {
+            // each product is below 2^62, so a u128 can take 2^66 of them
+            *acc += a_row
+                .iter()
+                .zip(b.iter())
+                .map(|(x, b_row)| u128::from(x * b_row[j]))
+                .sum::<u128>();
+        }
+    }
+}
+
+#[inline]
+fn reduce(acc: &Accumulator) -> Matrix3<Q231> {
+    let mut out = [[Mod231(0); 6]; 6];
+    for (out_row, row) in out.iter_mut().zip(acc.iter()) {
+        for (o, x) in out_row.iter_mut().zip(row.iter()) {
+            *o = Mod231(normalize_u128(*x));
+        }
+    }
+    join(&out)
+}
+
+/// Computes `a * b`
+pub(crate) fn mul_3x3(a: &Matrix3<Q231>, b: &Matrix3<Q231>) -> Matrix3<Q231> {
+    let mut acc = [[0; 6]; 6];
+    accumulate(&mut acc, &flatten(a), &flatten(b));
+    reduce(&acc)
+}
+
+/// Computes the sum of `a * b` over all pairs, reducing only once at
+/// the very end
+pub(crate) fn dot<I, A, B>(pairs: I) -> Matrix3<Q231>
+where
+    I: IntoIterator<Item = (A, B)>,
+    A: Borrow<Matrix3<Q231>>,
+    B: Borrow<Matrix3<Q231>>,
+{
+    let mut acc = [[0; 6]; 6];
+    for (a, b) in pairs {
+        accumulate(&mut acc, &flatten(a.borrow()), &flatten(b.borrow()));
+    }
+    reduce(&acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::MODULUS;
+    use quickcheck::{Arbitrary, Gen};
+
+    #[derive(Debug, Clone)]
+    struct M3Q231(Matrix3<Q231>);
+
+    impl Arbitrary for M3Q231 {
+        fn arbitrary<G: Gen>(g: &mut G) -> M3Q231 {
+            M3Q231(Matrix3::from_fn(|_, _| Q231::arbitrary(g)))
+        }
+    }
+
+    #[quickcheck]
+    fn prop_mul_matches_generic(a: M3Q231, b: M3Q231) -> bool {
+        mul_3x3(&a.0, &b.0) == a.0 * b.0
+    }
+
+    #[quickcheck]
+    fn prop_dot_matches_generic(pairs: Vec<(M3Q231, M3Q231)>) -> bool {
+        let expected = pairs
+            .iter()
+            .fold(Matrix3::zeros(), |acc, (a, b)| acc + a.0 * b.0);
+        dot(pairs.iter().map(|(a, b)| (&a.0, &b.0))) == expected
+    }
+
+    #[quickcheck]
+    fn prop_split_join_roundtrip(a: M3Q231) -> bool {
+        join(&split(&a.0)) == a.0
+    }
+
+    #[test]
+    fn mul_largest_coefficients() {
+        let max = Q231::from_entries([Mod231(MODULUS - 1); 4]);
+        let m = Matrix3::from_element(max);
+        assert_eq!(mul_3x3(&m, &m), m * m);
+    }
+}
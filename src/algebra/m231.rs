@@ -1,27 +1,67 @@
 use super::Invertible;
-use alga::general::{AbstractMagma, Additive, Identity, Multiplicative, TwoSidedInverse};
+use crate::error::Error;
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 use num_traits::identities::{One, Zero};
+#[cfg(any(test, feature = "testkit"))]
+use quickcheck::{Arbitrary, Gen};
 use rand::distributions::{Distribution, Standard};
 use rand::Rng;
-use serde::{Deserialize, Serialize};
-use std::fmt;
-use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+#[cfg(feature = "serde")]
+use serde::de::{Error as _, Unexpected};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize};
 
-const MODULUS: u32 = 2147483647u32; // 2^31 -1
-const MODULUSI32: i32 = 2147483647i32;
-const MODULUSI64: i64 = 2147483647i64;
+/// The prime 2^31 - 1
+pub const MODULUS: u32 = 2147483647u32; // 2^31 -1
 const MODULUSU64: u64 = 2147483647u64;
-const MAGIC229: usize = 536870912; // (MODULUS+1) / 4 = 2^29 - see try_sqrt for explanation
-
-#[derive(Clone, Copy, PartialEq, Alga, Serialize, Deserialize)]
-#[alga_traits(Ring(Additive, Multiplicative))]
-pub struct Mod231(pub u32);
+const MAGIC229: u64 = 536870912; // (MODULUS+1) / 4 = 2^29 - see sqrt for explanation
+
+/// Element of the prime field GF(2^31 - 1)
+///
+/// The value is always reduced, i.e. below `MODULUS`. Create elements with
+/// `Mod231::from`, which reduces any `u32`, and read them back with
+/// `value`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Mod231(pub(crate) u32);
+
+/// Unchecked serialized form of `Mod231`
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "Mod231")]
+struct RawMod231(u32);
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Mod231 {
+    /// Accepts only reduced values, below the modulus
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let RawMod231(x) = RawMod231::deserialize(deserializer)?;
+        if x < MODULUS {
+            Ok(Mod231(x))
+        } else {
+            Err(D::Error::invalid_value(
+                Unexpected::Unsigned(x.into()),
+                &"an integer below 2^31 - 1",
+            ))
+        }
+    }
+}
 
 impl Invertible for Mod231 {
     type Item = Mod231;
 
+    /// Inverts by Fermat's little theorem, `x^-1 = x^(MODULUS - 2)`
+    ///
+    /// The exponent is public, so the sequence of multiplications does not
+    /// depend on `self`; only the final zero check branches.
     fn try_invert(&self) -> Option<Self::Item> {
-        modinverse::modinverse(self.0 as i64, MODULUSI64).map(|x| Mod231(x as u32))
+        let inverse = num_traits::pow::pow(*self, (MODULUS - 2) as usize);
+        if self.is_zero() {
+            None
+        } else {
+            Some(inverse)
+        }
     }
 }
 
@@ -38,8 +78,15 @@ impl fmt::Display for Mod231 {
 }
 
 impl Distribution<Mod231> for Standard {
+    /// Samples uniformly, rejecting the one 31-bit value which is not
+    /// below the modulus
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Mod231 {
-        Mod231::from(rng.gen::<u32>())
+        loop {
+            let x = rng.gen::<u32>() & MODULUS;
+            if x != MODULUS {
+                return Mod231(x);
+            }
+        }
     }
 }
 
@@ -63,32 +110,45 @@ impl One for Mod231 {
     }
 }
 
+// All reductions below are branch-free, so that the time taken by
+// arithmetic on secret values does not depend on those values.
+
 #[inline]
-fn modulo(mut v: u32) -> u32 {
-    if v >= MODULUS {
-        v = (v >> 31) + (v & MODULUS);
-        while v >= MODULUS {
-            v -= MODULUS;
-        }
-    }
-    v
+// subtracts MODULUS from v if v >= MODULUS; requires v < 2^63
+fn reduce_once(v: u64) -> u64 {
+    let t = v.wrapping_sub(MODULUSU64);
+    // all ones if the subtraction wrapped around, i.e. if v < MODULUS
+    let mask = 0u64.wrapping_sub(t >> 63);
+    (v & mask) | (t & !mask)
 }
 
 #[inline]
-fn normalize_u64(mut v: u64) -> u32 {
-    if v >= MODULUSU64 {
-        v = (v >> 31) + (v & MODULUSU64);
-        while v >= MODULUSU64 {
-            v -= MODULUSU64;
-        }
-    }
-    v as u32
+fn fold(v: u64) -> u64 {
+    (v >> 31) + (v & MODULUSU64)
+}
+
+#[inline]
+fn modulo(v: u32) -> u32 {
+    reduce_once(fold(u64::from(v))) as u32
+}
+
+#[inline]
+pub(crate) fn normalize_u64(v: u64) -> u32 {
+    reduce_once(fold(fold(v))) as u32
+}
+
+#[inline]
+// reduces an accumulator of unreduced products, as used by the kernels
+pub(crate) fn normalize_u128(v: u128) -> u32 {
+    let fold = |v: u128| (v >> 31) + (v & u128::from(MODULUS));
+    normalize_u64(fold(fold(fold(v))) as u64)
 }
 
+#[cfg(any(test, feature = "fuzzing", feature = "experimental-normalize"))]
 #[inline]
 // should work for arguments up to (MODULUS-1)*(MODULUS-1)
 // which is the case for products of the form a * b where a,b < MODULUS
-fn normalize_product(mut v: u64) -> u32 {
+pub(crate) fn normalize_product(mut v: u64) -> u32 {
     v = (v >> 31) + (v & MODULUSU64);
     v = (v >> 31) + (v & MODULUSU64); // sic!
 
@@ -143,7 +203,7 @@ impl Neg for Mod231 {
     type Output = Mod231;
 
     fn neg(self) -> Self::Output {
-        Mod231(MODULUS - normalize(self.0))
+        Mod231(normalize(MODULUS - normalize(self.0)))
     }
 }
 
@@ -165,43 +225,74 @@ impl Div for Mod231 {
     type Output = Self;
 
     fn div(self, other: Self) -> Self::Output {
-        Self(normalize(self.0 * other.invert().0))
-    }
-}
-
-impl Identity<Additive> for Mod231 {
-    fn identity() -> Self {
-        Self::zero()
+        self * other.invert()
     }
 }
 
-impl TwoSidedInverse<Additive> for Mod231 {
-    fn two_sided_inverse(&self) -> Self {
-        Self::zero() - *self
-    }
-}
-
-impl Identity<Multiplicative> for Mod231 {
-    fn identity() -> Self {
-        Self::one()
+impl Mod231 {
+    /// The value as an integer below `MODULUS`
+    #[inline]
+    pub fn value(self) -> u32 {
+        self.0
+    }
+
+    /// Draws a uniformly distributed element from `rng`
+    #[inline]
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        rng.gen()
+    }
+
+    /// Raises to the power `exp`
+    #[inline]
+    pub fn pow(self, exp: u64) -> Self {
+        super::pow(self, exp)
+    }
+
+    /// Solves `x^2 = self`, returning the root `x` if one exists
+    ///
+    /// Since MODULUS = 3 (mod 4), the solution, if exists, is
+    /// x = a^((MODULUS+1)/4). The other root is `-x`.
+    ///
+    /// The exponentiation runs in constant time, but whether a root exists
+    /// is revealed by the result.
+    pub fn sqrt(self) -> Option<Self> {
+        let x = self.pow(MAGIC229);
+        if x * x == self {
+            Some(x)
+        } else {
+            None
+        }
     }
-}
 
-impl TwoSidedInverse<Multiplicative> for Mod231 {
-    fn two_sided_inverse(&self) -> Self {
-        Self::one() / *self
+    /// Legendre symbol: 1 for non-zero squares, -1 for non-squares and
+    /// 0 for zero
+    ///
+    /// Computed by Euler's criterion, `a^((MODULUS-1)/2)`.
+    pub fn legendre(self) -> i8 {
+        let x = self.pow(u64::from(MODULUS - 1) / 2);
+        if x.is_zero() {
+            0
+        } else if x.is_one() {
+            1
+        } else {
+            -1
+        }
     }
-}
 
-impl AbstractMagma<Additive> for Mod231 {
-    fn operate(&self, right: &Self) -> Self {
-        *self + *right
+    /// Divides by `other`, failing instead of panicking if it is zero
+    #[inline]
+    pub fn try_div(self, other: Self) -> Result<Self, Error> {
+        other
+            .try_invert()
+            .map(|inverse| self * inverse)
+            .ok_or(Error::NotInvertible)
     }
 }
 
-impl AbstractMagma<Multiplicative> for Mod231 {
-    fn operate(&self, right: &Self) -> Self {
-        *self * *right
+impl From<Mod231> for u32 {
+    #[inline]
+    fn from(x: Mod231) -> Self {
+        x.0
     }
 }
 
@@ -211,33 +302,22 @@ impl PartialEq<u32> for Mod231 {
     }
 }
 
-/// Try to solve the equation x^2 = a in our modular arithmetic
-/// Since MODULUS = 3 (mod 4), the solution, if exists, is x = a^((MODULUS+1)/4)
-pub fn try_sqrt(a: Mod231) -> Option<Mod231> {
-    let x = num_traits::pow::pow(a, MAGIC229);
-    if x * x == a {
-        Some(x)
-    } else {
-        None
+#[cfg(any(test, feature = "testkit"))]
+impl Arbitrary for Mod231 {
+    fn arbitrary<G: Gen>(g: &mut G) -> Mod231 {
+        let i = g.gen_range(0, MODULUS);
+        Mod231(i)
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(self.0.shrink().map(Mod231::from))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use quickcheck::{Arbitrary, Gen, TestResult};
-    use rand::Rng;
-
-    impl Arbitrary for Mod231 {
-        fn arbitrary<G: Gen>(g: &mut G) -> Mod231 {
-            let i = g.gen_range(0, MODULUS);
-            Mod231(i)
-        }
-
-        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
-            Box::new(self.0.shrink().map(Mod231::from))
-        }
-    }
+    use quickcheck::TestResult;
 
     #[quickcheck]
     fn prop_normalize(x: u32) -> bool {
@@ -249,6 +329,12 @@ mod tests {
         normalize_u64(x) == (x % MODULUSU64) as u32
     }
 
+    #[quickcheck]
+    fn prop_normalize_u128(hi: u64, lo: u64) -> bool {
+        let x = u128::from(hi) << 64 | u128::from(lo);
+        normalize_u128(x) == (x % u128::from(MODULUS)) as u32
+    }
+
     #[quickcheck]
     fn prop_normalize_product(a: u32, b: u32) -> TestResult {
         if a >= MODULUS || b >= MODULUS {
@@ -259,6 +345,59 @@ mod tests {
         TestResult::from_bool(prop)
     }
 
+    #[test]
+    fn normalize_extremes() {
+        for &x in &[MODULUS - 1, MODULUS, MODULUS + 1, u32::max_value()] {
+            assert_eq!(normalize(x), x % MODULUS);
+        }
+        for &x in &[
+            MODULUSU64 * MODULUSU64,
+            u64::max_value() - 1,
+            u64::max_value(),
+        ] {
+            assert_eq!(normalize_u64(x), (x % MODULUSU64) as u32);
+        }
+        for &x in &[u128::from(u64::max_value()) << 4, u128::max_value()] {
+            assert_eq!(normalize_u128(x), (x % u128::from(MODULUS)) as u32);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_rejects_unreduced() {
+        assert_eq!(serde_json::from_str::<Mod231>("5").unwrap(), Mod231(5));
+        let err = serde_json::from_str::<Mod231>("2147483647").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected an integer below 2^31 - 1"));
+        assert!(serde_json::from_str::<Mod231>("-1").is_err());
+    }
+
+    #[test]
+    fn negate_zero_is_zero() {
+        assert_eq!(Mod231(0), -Mod231(0));
+    }
+
+    #[quickcheck]
+    fn prop_invert_matches_extended_euclid(x: Mod231) -> TestResult {
+        if x == Mod231(0) {
+            return TestResult::discard();
+        }
+
+        let (mut r0, mut r1) = (i64::from(MODULUS), i64::from(x.0));
+        let (mut t0, mut t1) = (0i64, 1i64);
+        while r1 != 0 {
+            let q = r0 / r1;
+            let (r, t) = (r0 - q * r1, t0 - q * t1);
+            r0 = r1;
+            r1 = r;
+            t0 = t1;
+            t1 = t;
+        }
+        let expected = t0.rem_euclid(i64::from(MODULUS)) as u32;
+        TestResult::from_bool(x.invert() == Mod231(expected))
+    }
+
     #[quickcheck]
     fn double_negate_is_identity(x: Mod231) -> bool {
         x == x.neg().neg()
@@ -273,15 +412,58 @@ mod tests {
         TestResult::from_bool(x * x.invert() == Mod231(1))
     }
 
+    #[quickcheck]
+    fn prop_div_is_mul_by_inverse(x: Mod231, y: Mod231) -> TestResult {
+        if y == Mod231(0) {
+            return TestResult::discard();
+        }
+        // a product of two residues used to overflow `u32` here
+        TestResult::from_bool(x / y * y == x && x.try_div(y).ok() == Some(x / y))
+    }
+
+    #[test]
+    fn div_by_zero_fails() {
+        assert!(matches!(
+            Mod231(5).try_div(Mod231(0)),
+            Err(Error::NotInvertible)
+        ));
+        assert_eq!(Mod231(MODULUS - 1) / Mod231(MODULUS - 1), Mod231(1));
+    }
+
     #[test]
     fn test_sqrt() {
         for i in 10..20 {
             let a = Mod231(i);
-            let r = try_sqrt(a);
+            let r = a.sqrt();
             println!("a = {}, r = {:?}", a, r);
             if let Some(x) = r {
                 assert_eq!(x * x, a);
             }
         }
     }
+
+    #[quickcheck]
+    fn prop_sqrt_matches_legendre(x: Mod231) -> bool {
+        let square = x * x;
+        square.sqrt().map(|r| r * r) == Some(square)
+            && square.legendre() == if x.is_zero() { 0 } else { 1 }
+            && x.sqrt().is_some() == (x.legendre() >= 0)
+            // -1 is not a square, since MODULUS = 3 (mod 4)
+            && (-square).legendre() == -square.legendre()
+    }
+
+    #[quickcheck]
+    fn prop_pow_matches_repeated_mul(x: Mod231, exp: u8) -> bool {
+        let expected = (0..exp).fold(Mod231(1), |acc, _| acc * x);
+        x.pow(u64::from(exp)) == expected
+            && x.pow(u64::from(MODULUS) - 1) == if x.is_zero() { x } else { Mod231(1) }
+    }
+
+    #[test]
+    fn random_is_reduced() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            assert!(Mod231::random(&mut rng).value() < MODULUS);
+        }
+    }
 }
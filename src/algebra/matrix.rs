@@ -0,0 +1,357 @@
+//! Fixed-size matrices over the crate's rings
+//!
+//! Entries are stored in column-major order in a plain array, and are
+//! (de)serialized as a flat sequence in that same order, which is the
+//! format ciphertexts and keys have always had on the wire.
+use super::Ring;
+use core::fmt::{self, Write};
+use core::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub};
+use num_traits::{One, Zero};
+#[cfg(feature = "serde")]
+use serde::de::{Error as _, IgnoredAny, SeqAccess, Visitor};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeSeq;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Counts the characters written to it
+struct Width(usize);
+
+impl Write for Width {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.chars().count();
+        Ok(())
+    }
+}
+
+/// Number of characters `x` takes when displayed
+fn width<T: fmt::Display>(x: &T) -> usize {
+    let mut width = Width(0);
+    // writing to `Width` never fails
+    let _ = write!(width, "{}", x);
+    width.0
+}
+
+macro_rules! matrix {
+    ($(#[$attr:meta])* $name:ident, $rows:expr, $cols:expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        pub struct $name<T> {
+            data: [T; $rows * $cols],
+        }
+
+        impl<T: Copy> $name<T> {
+            /// Builds the matrix by evaluating `f(row, column)` for every
+            /// entry, in column-major order
+            #[inline]
+            pub fn from_fn<F: FnMut(usize, usize) -> T>(mut f: F) -> Self {
+                Self {
+                    data: core::array::from_fn(|i| f(i % $rows, i / $rows)),
+                }
+            }
+
+            /// Matrix with every entry equal to `x`
+            #[inline]
+            pub fn from_element(x: T) -> Self {
+                Self {
+                    data: [x; $rows * $cols],
+                }
+            }
+
+            /// Applies `f` to every entry
+            #[inline]
+            pub fn map<U: Copy, F: FnMut(T) -> U>(&self, mut f: F) -> $name<U> {
+                $name::from_fn(|r, c| f(self[(r, c)]))
+            }
+        }
+
+        impl<T: Ring> $name<T> {
+            /// Matrix with every entry zero
+            #[inline]
+            pub fn zeros() -> Self {
+                Self::from_element(T::zero())
+            }
+        }
+
+        impl<T> Index<usize> for $name<T> {
+            type Output = T;
+
+            #[inline]
+            fn index(&self, index: usize) -> &T {
+                &self.data[index]
+            }
+        }
+
+        impl<T> IndexMut<usize> for $name<T> {
+            #[inline]
+            fn index_mut(&mut self, index: usize) -> &mut T {
+                &mut self.data[index]
+            }
+        }
+
+        impl<T> Index<(usize, usize)> for $name<T> {
+            type Output = T;
+
+            #[inline]
+            fn index(&self, (r, c): (usize, usize)) -> &T {
+                assert!(r < $rows && c < $cols, "matrix index out of bounds");
+                &self.data[c * $rows + r]
+            }
+        }
+
+        impl<T> IndexMut<(usize, usize)> for $name<T> {
+            #[inline]
+            fn index_mut(&mut self, (r, c): (usize, usize)) -> &mut T {
+                assert!(r < $rows && c < $cols, "matrix index out of bounds");
+                &mut self.data[c * $rows + r]
+            }
+        }
+
+        impl<T: Ring> Add for $name<T> {
+            type Output = Self;
+
+            #[inline]
+            fn add(self, rhs: Self) -> Self {
+                Self::from_fn(|r, c| self[(r, c)] + rhs[(r, c)])
+            }
+        }
+
+        impl<T: Ring> AddAssign for $name<T> {
+            #[inline]
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs
+            }
+        }
+
+        impl<T: Ring> Sub for $name<T> {
+            type Output = Self;
+
+            #[inline]
+            fn sub(self, rhs: Self) -> Self {
+                Self::from_fn(|r, c| self[(r, c)] - rhs[(r, c)])
+            }
+        }
+
+        impl<T: Ring> Neg for $name<T> {
+            type Output = Self;
+
+            #[inline]
+            fn neg(self) -> Self {
+                self.map(|x| -x)
+            }
+        }
+
+        impl<T: Ring> Zero for $name<T> {
+            #[inline]
+            fn zero() -> Self {
+                Self::zeros()
+            }
+
+            #[inline]
+            fn is_zero(&self) -> bool {
+                self.data.iter().all(Zero::is_zero)
+            }
+        }
+
+        impl<T: fmt::Display> fmt::Display for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let max = self.data.iter().map(width).max().unwrap_or(0);
+                let inner = (max + 1) * $cols - 1;
+
+                writeln!(f)?;
+                writeln!(f, "  ┌ {:>w$} ┐", "", w = inner)?;
+                for r in 0..$rows {
+                    write!(f, "  │")?;
+                    for c in 0..$cols {
+                        let x = &self[(r, c)];
+                        write!(f, " {:>w$}{}", "", x, w = max - width(x))?;
+                    }
+                    writeln!(f, " │")?;
+                }
+                writeln!(f, "  └ {:>w$} ┘", "", w = inner)?;
+                writeln!(f)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<T: Serialize> Serialize for $name<T> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut seq = serializer.serialize_seq(Some($rows * $cols))?;
+                for x in self.data.iter() {
+                    seq.serialize_element(x)?;
+                }
+                seq.end()
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, T: Deserialize<'de> + Ring> Deserialize<'de> for $name<T> {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct Entries<T>(core::marker::PhantomData<T>);
+
+                impl<'de, T: Deserialize<'de> + Ring> Visitor<'de> for Entries<T> {
+                    type Value = [T; $rows * $cols];
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "a sequence of {} entries", $rows * $cols)
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                        let mut data = [T::zero(); $rows * $cols];
+                        for (i, x) in data.iter_mut().enumerate() {
+                            *x = seq
+                                .next_element()?
+                                .ok_or_else(|| A::Error::invalid_length(i, &self))?;
+                        }
+                        if seq.next_element::<IgnoredAny>()?.is_some() {
+                            return Err(A::Error::invalid_length($rows * $cols + 1, &self));
+                        }
+                        Ok(data)
+                    }
+                }
+
+                let data = deserializer.deserialize_seq(Entries(core::marker::PhantomData))?;
+                Ok(Self { data })
+            }
+        }
+    };
+}
+
+macro_rules! square {
+    ($name:ident, $n:expr) => {
+        impl<T: Ring> $name<T> {
+            /// Diagonal matrix with `x` on the diagonal
+            #[inline]
+            pub fn from_diagonal_element(x: T) -> Self {
+                Self::from_fn(|r, c| if r == c { x } else { T::zero() })
+            }
+
+            /// Identity matrix
+            #[inline]
+            pub fn identity() -> Self {
+                Self::from_diagonal_element(T::one())
+            }
+        }
+
+        impl<T: Ring> Mul for $name<T> {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self {
+                Self::from_fn(|r, c| {
+                    (0..$n).fold(T::zero(), |acc, k| acc + self[(r, k)] * rhs[(k, c)])
+                })
+            }
+        }
+
+        impl<T: Ring> One for $name<T> {
+            #[inline]
+            fn one() -> Self {
+                Self::identity()
+            }
+        }
+    };
+}
+
+matrix!(
+    /// 2x2 matrix
+    Matrix2,
+    2,
+    2
+);
+square!(Matrix2, 2);
+
+matrix!(
+    /// 3x3 matrix
+    Matrix3,
+    3,
+    3
+);
+square!(Matrix3, 3);
+
+#[cfg(feature = "serde")]
+matrix!(
+    /// Column vector of length 4
+    Vector4,
+    4,
+    1
+);
+
+impl<T: Copy> Matrix2<T> {
+    /// Creates the matrix from its entries in row-major order
+    #[inline]
+    pub fn new(m00: T, m01: T, m10: T, m11: T) -> Self {
+        Self {
+            data: [m00, m10, m01, m11],
+        }
+    }
+}
+
+impl<T: Copy> Matrix3<T> {
+    /// Creates the matrix from its entries in row-major order
+    #[allow(clippy::too_many_arguments)]
+    #[inline]
+    pub fn new(m00: T, m01: T, m02: T, m10: T, m11: T, m12: T, m20: T, m21: T, m22: T) -> Self {
+        Self {
+            data: [m00, m10, m20, m01, m11, m21, m02, m12, m22],
+        }
+    }
+}
+
+impl<T: Ring> Matrix3<T> {
+    /// Copy of the matrix with the entries below the diagonal set to zero
+    #[inline]
+    pub fn upper_triangle(&self) -> Self {
+        Self::from_fn(|r, c| if r > c { T::zero() } else { self[(r, c)] })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Copy> Vector4<T> {
+    #[inline]
+    pub fn new(x: T, y: T, z: T, w: T) -> Self {
+        Self { data: [x, y, z, w] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::Mod231;
+
+    fn m3(entries: [u32; 9]) -> Matrix3<Mod231> {
+        Matrix3::from_fn(|r, c| Mod231(entries[3 * r + c]))
+    }
+
+    #[test]
+    fn new_is_row_major_storage_column_major() {
+        let m = Matrix3::new(1, 2, 3, 4, 5, 6, 7, 8, 9);
+        assert_eq!(m[(0, 1)], 2);
+        assert_eq!(m[(1, 0)], 4);
+        let column_major: Vec<_> = (0..9).map(|i| m[i]).collect();
+        assert_eq!(column_major, [1, 4, 7, 2, 5, 8, 3, 6, 9]);
+    }
+
+    #[test]
+    fn product() {
+        let a = m3([1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let b = m3([9, 8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(a * b, m3([30, 24, 18, 84, 69, 54, 138, 114, 90]));
+        assert_eq!(a * Matrix3::identity(), a);
+    }
+
+    #[test]
+    fn upper_triangle() {
+        let a = m3([1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(a.upper_triangle(), m3([1, 2, 3, 0, 5, 6, 0, 0, 9]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_is_flat_column_major() {
+        let a = m3([1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, "[1,4,7,2,5,8,3,6,9]");
+        assert_eq!(serde_json::from_str::<Matrix3<Mod231>>(&json).unwrap(), a);
+        assert!(serde_json::from_str::<Matrix3<Mod231>>("[1,2,3]").is_err());
+    }
+}
@@ -1,15 +1,91 @@
+//! Arithmetic in GF(2^31 - 1) and in the quaternions over it
+//!
+//! These are the types the scheme computes with, exposed so that encoders
+//! and verification code can do the same arithmetic on plaintexts:
+//!
+//! * `Mod231` - the prime field GF(2^31 - 1), with `pow`, `sqrt`,
+//!   `legendre`, division and inversion through `Invertible`
+//! * `QuaternionM` - quaternions `w + xi + yj + zk` over any `Ring`,
+//!   with conjugate, norm, `Div`, `pow` and inversion
+//! * `Q231` - quaternions over `Mod231` stored in split form, i.e. as
+//!   2x2 matrices, which is what ciphertexts are made of; it converts to
+//!   and from `[[Mod231; 2]; 2]`
+//! * `C231` - complex numbers over `Mod231`, forming the field
+//!   GF((2^31 - 1)^2), embedded in `Q231` as the span of `1` and `i`
+//! * `Matrix2`, `Matrix3` - fixed-size matrices over any `Ring`; a
+//!   ciphertext is a `Matrix3<Q231>`, which is also what a
+//!   `NoiseStrategy` returns
+//!
+//! ## Example:
+//!
+//! ```
+//! use gmorph::algebra::{Invertible, Mod231, QuaternionM, MODULUS};
+//!
+//! let x = Mod231::from(3);
+//! assert_eq!(x.pow(u64::from(MODULUS) - 1), Mod231::from(1));
+//! assert_eq!(x * x.invert(), Mod231::from(1));
+//! assert_eq!((x * x).sqrt().map(|r| r * r), Some(x * x));
+//!
+//! let q = QuaternionM::new(x, Mod231::from(1), Mod231::from(4), Mod231::from(1));
+//! assert_eq!(q * q.conjugate(), QuaternionM::from_real(q.norm2()));
+//! assert_eq!(q.pow(2) / q, q);
+//! ```
+mod c231;
+mod kernel;
 mod m231;
+mod matrix;
 mod q231;
 mod quaternion;
 
-use nalgebra::{dimension::U2, storage::Storage, Matrix, Matrix2, Matrix3};
+use core::ops::{AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use num_traits::{One, Zero};
 
-pub(crate) use self::m231::Mod231;
-pub(crate) use self::q231::Q231;
+pub use self::c231::C231;
+pub(crate) use self::kernel::{dot, mul_3x3};
+#[cfg(feature = "alloc")]
+pub(crate) use self::kernel::{join, split};
+pub(crate) use self::m231::normalize_u64;
+#[cfg(feature = "fuzzing")]
+pub(crate) use self::m231::{normalize_product, normalize_u128};
+pub use self::m231::{Mod231, MODULUS};
+pub use self::matrix::{Matrix2, Matrix3};
+pub use self::q231::Q231;
+pub use self::quaternion::QuaternionM;
 
-pub(crate) trait Invertible {
+/// Ring operations the generic quaternion and matrix types are built on
+pub trait Ring:
+    Copy
+    + PartialEq
+    + Zero
+    + One
+    + Neg<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+{
+}
+
+impl<T> Ring for T where
+    T: Copy
+        + PartialEq
+        + Zero
+        + One
+        + Neg<Output = T>
+        + Sub<Output = T>
+        + Mul<Output = T>
+        + AddAssign
+        + SubAssign
+        + MulAssign
+{
+}
+
+/// Types whose elements may have a multiplicative inverse
+pub trait Invertible {
     type Item;
 
+    /// The inverse, or `None` for zero and other non-invertible elements
     fn try_invert(&self) -> Option<Self::Item>;
 
     /// Will panic if `try_invert` fails
@@ -19,13 +95,28 @@ pub(crate) trait Invertible {
     }
 }
 
-pub(crate) fn invert_2x2<S: Storage<Q231, U2, U2>>(
-    matrix: &Matrix<Q231, U2, U2, S>,
-) -> Option<Matrix2<Q231>> {
-    let a = matrix[0];
-    let b = matrix[2];
-    let c = matrix[1];
-    let d = matrix[3];
+/// Raises `base` to the power `exp` by square-and-multiply
+///
+/// The exponent is public, so branching on its bits does not depend on
+/// `base`.
+pub(crate) fn pow<T: Copy + One + Mul<Output = T>>(base: T, mut exp: u64) -> T {
+    let mut acc = T::one();
+    let mut square = base;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc * square;
+        }
+        square = square * square;
+        exp >>= 1;
+    }
+    acc
+}
+
+pub(crate) fn invert_2x2(matrix: &Matrix2<Q231>) -> Option<Matrix2<Q231>> {
+    let a = matrix[(0, 0)];
+    let b = matrix[(0, 1)];
+    let c = matrix[(1, 0)];
+    let d = matrix[(1, 1)];
     let a_inv = a.try_invert()?;
     let d_inv = d.try_invert()?;
 
@@ -43,27 +134,44 @@ pub(crate) fn invert_2x2<S: Storage<Q231, U2, U2>>(
 }
 
 pub(crate) fn invert_3x3(matrix: &Matrix3<Q231>) -> Option<Matrix3<Q231>> {
-    use nalgebra::dimension::U1;
-
-    let a = matrix[0];
-    let b = matrix.fixed_slice::<U1, U2>(0, 1);
-    let c = matrix.fixed_slice::<U2, U1>(1, 0);
-    let d = matrix.fixed_slice::<U2, U2>(1, 1);
+    // blocks [[a, b], [c, d]] with a 1x1, b 1x2, c 2x1 and d 2x2
+    let a = matrix[(0, 0)];
+    let b = [matrix[(0, 1)], matrix[(0, 2)]];
+    let c = [matrix[(1, 0)], matrix[(2, 0)]];
+    let d = Matrix2::from_fn(|r, c| matrix[(r + 1, c + 1)]);
 
     let a_inv = a.try_invert()?;
     let d_inv = invert_2x2(&d)?;
 
-    let x = a - (b * d_inv * c)[0];
-    let y = d - c * a_inv * b;
+    // b * d^-1 and d^-1 * c
+    let bd = [
+        b[0] * d_inv[(0, 0)] + b[1] * d_inv[(1, 0)],
+        b[0] * d_inv[(0, 1)] + b[1] * d_inv[(1, 1)],
+    ];
+    let dc = [
+        d_inv[(0, 0)] * c[0] + d_inv[(0, 1)] * c[1],
+        d_inv[(1, 0)] * c[0] + d_inv[(1, 1)] * c[1],
+    ];
+
+    let x = a - (bd[0] * c[0] + bd[1] * c[1]);
+    let y = d - Matrix2::from_fn(|r, s| c[r] * a_inv * b[s]);
 
     let x_inv = x.try_invert()?;
     let y_inv = invert_2x2(&y)?;
 
-    let r1 = (b * d_inv).map(|x| -x_inv * x);
-    let r2 = (-d_inv * c).map(|x| x * x_inv);
+    let r1 = [-x_inv * bd[0], -x_inv * bd[1]];
+    let r2 = [-dc[0] * x_inv, -dc[1] * x_inv];
 
     let m = Matrix3::new(
-        x_inv, r1[0], r1[1], r2[0], y_inv[0], y_inv[2], r2[1], y_inv[1], y_inv[3],
+        x_inv,
+        r1[0],
+        r1[1],
+        r2[0],
+        y_inv[(0, 0)],
+        y_inv[(0, 1)],
+        r2[1],
+        y_inv[(1, 0)],
+        y_inv[(1, 1)],
     );
 
     Some(m)
@@ -1,11 +1,185 @@
-use super::{invert_3x3, m231::try_sqrt, m231::Mod231, quaternion::QuaternionM, Invertible};
-use nalgebra::{Matrix3, Vector4};
-use num_traits::Zero;
+//! Quaternions over GF(2^31 - 1) in split form
+//!
+//! The quaternion algebra over GF(p) is isomorphic to the algebra of 2x2
+//! matrices over GF(p). `Q231` stores the matrix, since a product then
+//! takes 8 multiplications in the field instead of the 16 needed by
+//! `QuaternionM`. The isomorphism sends
+//!
+//! ```text
+//! i -> [ A  B ]    j -> [  0  1 ]    k = ij -> [ -B  A ]
+//!      [ B -A ]         [ -1  0 ]              [  A  B ]
+//! ```
+//!
+//! where `A^2 + B^2 = -1`. Conversions to and from quaternion coordinates
+//! happen only when encoding plaintexts and when (de)serializing, which
+//! goes through `QuaternionM<Mod231>` so that the wire format is unchanged.
+#[cfg(test)]
+use super::invert_3x3;
+use super::{m231::Mod231, matrix::Matrix3, quaternion::QuaternionM, Invertible};
+use crate::error::Error;
+#[cfg(feature = "std")]
+use crate::noise::{NoiseStrategy, Triangular};
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use num_traits::{One, Zero};
+#[cfg(any(test, feature = "testkit"))]
+use quickcheck::{Arbitrary, Gen};
 use rand::distributions::{Distribution, Standard};
-use rand::{thread_rng, Rng};
-use std::ops::Neg;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// A^2 + B^2 = -1 (mod 2^31 - 1)
+const A: Mod231 = Mod231(2);
+const B: Mod231 = Mod231(105948780);
+// 1/2 (mod 2^31 - 1)
+const HALF: Mod231 = Mod231(1073741824);
+
+/// Quaternion over GF(2^31 - 1), stored as the 2x2 matrix
+/// `[[m00, m01], [m10, m11]]`
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(from = "QuaternionM<Mod231>", into = "QuaternionM<Mod231>")
+)]
+pub struct Q231 {
+    m00: Mod231,
+    m01: Mod231,
+    m10: Mod231,
+    m11: Mod231,
+}
+
+impl Q231 {
+    /// Creates the quaternion `w + xi + yj + zk`
+    #[inline]
+    pub fn new(w: Mod231, x: Mod231, y: Mod231, z: Mod231) -> Self {
+        let u = A * x - B * z;
+        let v = B * x + A * z;
+        Self {
+            m00: w + u,
+            m01: v + y,
+            m10: v - y,
+            m11: w - u,
+        }
+    }
+
+    /// Creates the real quaternion `w`, i.e. `w` times the identity
+    #[inline]
+    pub fn from_real(w: Mod231) -> Self {
+        Self {
+            m00: w,
+            m01: Mod231::zero(),
+            m10: Mod231::zero(),
+            m11: w,
+        }
+    }
+
+    /// Real part of the quaternion, i.e. half the trace of the matrix
+    #[inline]
+    pub fn real(&self) -> Mod231 {
+        (self.m00 + self.m11) * HALF
+    }
+
+    /// Quaternion conjugate, i.e. the adjugate of the matrix
+    #[inline]
+    pub fn conjugate(&self) -> Self {
+        Self {
+            m00: self.m11,
+            m01: -self.m01,
+            m10: -self.m10,
+            m11: self.m00,
+        }
+    }
+
+    /// Quaternion norm, i.e. the determinant of the matrix
+    #[inline]
+    pub fn norm2(&self) -> Mod231 {
+        self.m00 * self.m11 - self.m01 * self.m10
+    }
+
+    /// Multiplies every coordinate by the scalar `w`
+    #[inline]
+    pub fn scale(&self, w: Mod231) -> Self {
+        Self {
+            m00: self.m00 * w,
+            m01: self.m01 * w,
+            m10: self.m10 * w,
+            m11: self.m11 * w,
+        }
+    }
+
+    /// Raises to the power `exp`
+    #[inline]
+    pub fn pow(&self, exp: u64) -> Self {
+        super::pow(*self, exp)
+    }
+
+    /// Right division `self * other^-1`, failing instead of panicking if
+    /// `other` is not invertible
+    #[inline]
+    pub fn try_div(&self, other: &Self) -> Result<Self, Error> {
+        other
+            .try_invert()
+            .map(|inverse| *self * inverse)
+            .ok_or(Error::NotInvertible)
+    }
+}
+
+impl Q231 {
+    /// Matrix entries in row-major order
+    #[inline]
+    pub(crate) fn entries(&self) -> [Mod231; 4] {
+        [self.m00, self.m01, self.m10, self.m11]
+    }
+
+    /// Builds the quaternion from matrix entries in row-major order
+    #[inline]
+    pub(crate) fn from_entries(entries: [Mod231; 4]) -> Self {
+        Self {
+            m00: entries[0],
+            m01: entries[1],
+            m10: entries[2],
+            m11: entries[3],
+        }
+    }
+}
+
+impl From<QuaternionM<Mod231>> for Q231 {
+    #[inline]
+    fn from(q: QuaternionM<Mod231>) -> Self {
+        Self::new(q.w, q.i, q.j, q.k)
+    }
+}
 
-pub(crate) type Q231 = QuaternionM<Mod231>;
+impl From<Q231> for QuaternionM<Mod231> {
+    fn from(q: Q231) -> Self {
+        let p = (q.m00 - q.m11) * HALF;
+        let r = (q.m01 + q.m10) * HALF;
+        let y = (q.m01 - q.m10) * HALF;
+        let x = -(A * p + B * r);
+        let z = B * p - A * r;
+        QuaternionM::new(q.real(), x, y, z)
+    }
+}
+
+impl From<[[Mod231; 2]; 2]> for Q231 {
+    /// The quaternion whose split form is the given matrix, row by row
+    #[inline]
+    fn from(m: [[Mod231; 2]; 2]) -> Self {
+        Self::from_entries([m[0][0], m[0][1], m[1][0], m[1][1]])
+    }
+}
+
+impl From<Q231> for [[Mod231; 2]; 2] {
+    /// The split form of the quaternion, row by row
+    #[inline]
+    fn from(q: Q231) -> Self {
+        [[q.m00, q.m01], [q.m10, q.m11]]
+    }
+}
 
 impl From<Mod231> for Q231 {
     fn from(w: Mod231) -> Self {
@@ -31,78 +205,212 @@ impl From<Matrix3<Q231>> for Q231 {
 
 impl Distribution<Q231> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Q231 {
-        Q231::from(Vector4::<Mod231>::zero().map(|_| rng.gen::<Mod231>()))
+        Q231 {
+            m00: rng.gen(),
+            m01: rng.gen(),
+            m10: rng.gen(),
+            m11: rng.gen(),
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl Into<Matrix3<Q231>> for Q231 {
     fn into(self) -> Matrix3<Q231> {
-        let mut noise = Matrix3::<Q231>::zeros()
-            .map(|_| thread_rng().gen::<Q231>())
-            .upper_triangle();
-        // Make sure the middle element (and hence the matrix) is noninvertible
-        // The loop should not run many times - statistically every 2nd element has a square root
-        loop {
-            let c: Q231 = thread_rng().gen::<Q231>();
-            let y: Mod231 = c.i * c.i + c.j * c.j + c.k * c.k;
-            if let Some(x) = try_sqrt(y.neg()) {
-                noise[4] = Q231::new(x, c.i, c.j, c.k);
-                assert_eq!(noise[4].norm2(), Mod231(0));
-                break;
-            }
+        Triangular.embed(self, &mut thread_rng())
+    }
+}
+
+impl fmt::Debug for Q231 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&QuaternionM::from(*self), f)
+    }
+}
+
+impl fmt::Display for Q231 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl Add for Q231 {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        Self {
+            m00: self.m00 + other.m00,
+            m01: self.m01 + other.m01,
+            m10: self.m10 + other.m10,
+            m11: self.m11 + other.m11,
+        }
+    }
+}
+
+impl AddAssign for Q231 {
+    #[inline]
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other
+    }
+}
+
+impl Sub for Q231 {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        Self {
+            m00: self.m00 - other.m00,
+            m01: self.m01 - other.m01,
+            m10: self.m10 - other.m10,
+            m11: self.m11 - other.m11,
         }
-        noise[0] = self;
-        noise
+    }
+}
+
+impl SubAssign for Q231 {
+    #[inline]
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other
+    }
+}
+
+impl Neg for Q231 {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self {
+            m00: -self.m00,
+            m01: -self.m01,
+            m10: -self.m10,
+            m11: -self.m11,
+        }
+    }
+}
+
+impl Mul for Q231 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, other: Self) -> Self {
+        Self {
+            m00: self.m00 * other.m00 + self.m01 * other.m10,
+            m01: self.m00 * other.m01 + self.m01 * other.m11,
+            m10: self.m10 * other.m00 + self.m11 * other.m10,
+            m11: self.m10 * other.m01 + self.m11 * other.m11,
+        }
+    }
+}
+
+impl MulAssign for Q231 {
+    #[inline]
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other
+    }
+}
+
+impl Div for Q231 {
+    type Output = Self;
+
+    /// Right division `self * other^-1`
+    ///
+    /// Panics if `other` is not invertible, see `try_div`.
+    #[inline]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, other: Self) -> Self {
+        self * other.invert()
+    }
+}
+
+impl Zero for Q231 {
+    #[inline]
+    fn zero() -> Self {
+        Self::from_real(Mod231::zero())
+    }
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        self.m00.is_zero() && self.m01.is_zero() && self.m10.is_zero() && self.m11.is_zero()
+    }
+}
+
+impl One for Q231 {
+    #[inline]
+    fn one() -> Self {
+        Self::from_real(Mod231::one())
+    }
+
+    #[inline]
+    fn is_one(&self) -> bool {
+        *self == Self::one()
+    }
+}
+
+#[cfg(any(test, feature = "testkit"))]
+impl Arbitrary for Q231 {
+    fn arbitrary<G: Gen>(g: &mut G) -> Q231 {
+        Q231::new(
+            Mod231::arbitrary(g),
+            Mod231::arbitrary(g),
+            Mod231::arbitrary(g),
+            Mod231::arbitrary(g),
+        )
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let q = QuaternionM::from(*self);
+        let iter = std::iter::empty();
+        let iter = iter.chain(q.w.shrink().map(move |w| Q231::new(w, q.i, q.j, q.k)));
+        let iter = iter.chain(q.i.shrink().map(move |i| Q231::new(q.w, i, q.j, q.k)));
+        let iter = iter.chain(q.j.shrink().map(move |j| Q231::new(q.w, q.i, j, q.k)));
+        let iter = iter.chain(q.k.shrink().map(move |k| Q231::new(q.w, q.i, q.j, k)));
+        Box::new(iter)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use nalgebra::Vector3;
-    use num_traits::{One, Zero};
-    use quickcheck::{Arbitrary, Gen, TestResult};
-
-    impl Arbitrary for Q231 {
-        fn arbitrary<G: Gen>(g: &mut G) -> Q231 {
-            Q231::new(
-                Mod231::arbitrary(g),
-                Mod231::arbitrary(g),
-                Mod231::arbitrary(g),
-                Mod231::arbitrary(g),
-            )
-        }
+    use quickcheck::TestResult;
+
+    #[derive(Debug, Clone)]
+    struct Quaternion(QuaternionM<Mod231>);
 
-        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
-            let iter = std::iter::empty();
-            let cloned = self.clone();
-            let iter = iter.chain(self.w.shrink().map(move |w| {
-                let mut q = cloned.clone();
-                q.w = w;
-                q
-            }));
-            let cloned = self.clone();
-            let iter = iter.chain(self.i.shrink().map(move |i| {
-                let mut q = cloned.clone();
-                q.i = i;
-                q
-            }));
-            let cloned = self.clone();
-            let iter = iter.chain(self.j.shrink().map(move |j| {
-                let mut q = cloned.clone();
-                q.j = j;
-                q
-            }));
-            let cloned = self.clone();
-            let iter = iter.chain(self.k.shrink().map(move |k| {
-                let mut q = cloned.clone();
-                q.k = k;
-                q
-            }));
-            Box::new(iter)
+    impl Arbitrary for Quaternion {
+        fn arbitrary<G: Gen>(g: &mut G) -> Quaternion {
+            Quaternion(QuaternionM::from(Q231::arbitrary(g)))
         }
     }
 
+    #[test]
+    fn split_constants() {
+        assert_eq!(A * A + B * B, -Mod231::one());
+        assert_eq!(HALF + HALF, Mod231::one());
+    }
+
+    #[quickcheck]
+    fn prop_split_roundtrip(a: Quaternion) -> bool {
+        QuaternionM::from(Q231::from(a.0)) == a.0
+    }
+
+    #[quickcheck]
+    fn prop_split_mul_homomorphic(a: Quaternion, b: Quaternion) -> bool {
+        Q231::from(a.0 * b.0) == Q231::from(a.0) * Q231::from(b.0)
+    }
+
+    #[quickcheck]
+    fn prop_split_norm_and_conjugate(a: Quaternion) -> bool {
+        let q = Q231::from(a.0);
+        q.norm2() == a.0.norm2() && q.conjugate() == Q231::from(a.0.conjugate())
+    }
+
+    #[cfg(feature = "serde")]
+    #[quickcheck]
+    fn prop_wire_format_unchanged(a: Quaternion) -> bool {
+        serde_json::to_string(&Q231::from(a.0)).unwrap() == serde_json::to_string(&a.0).unwrap()
+    }
+
     #[quickcheck]
     fn add_commutative(a: Q231, b: Q231) -> bool {
         a + b == b + a
@@ -110,12 +418,12 @@ mod tests {
 
     #[quickcheck]
     fn prop_conjugate1(a: Q231) -> bool {
-        a + a.conjugate() == Q231::from(Mod231::from(2) * a.w)
+        a + a.conjugate() == Q231::from(Mod231::from(2) * a.real())
     }
 
     #[quickcheck]
     fn prop_conjugate2(a: Q231) -> bool {
-        (a + a.conjugate()).imag() == Vector3::zero()
+        QuaternionM::from(a + a.conjugate()).imag() == [Mod231::zero(); 3]
     }
 
     #[quickcheck]
@@ -135,6 +443,31 @@ mod tests {
         }
     }
 
+    #[quickcheck]
+    fn prop_split_div_and_pow(a: Quaternion, b: Quaternion, exp: u8) -> TestResult {
+        let (qa, qb) = (Q231::from(a.0), Q231::from(b.0));
+        let exp = u64::from(exp);
+        let pow = qa.pow(exp) == Q231::from(a.0.pow(exp));
+        match b.0.try_invert() {
+            None => TestResult::from_bool(
+                pow && qb.try_invert().is_none() && a.0.try_div(&b.0).is_err(),
+            ),
+            Some(_) => TestResult::from_bool(
+                pow && Q231::from(a.0 / b.0) == qa / qb && (qa / qb) * qb == qa,
+            ),
+        }
+    }
+
+    #[quickcheck]
+    fn prop_quaternion_inverse(a: Quaternion) -> TestResult {
+        match a.0.try_invert() {
+            None => TestResult::from_bool(a.0.norm2().is_zero()),
+            Some(inverse) => TestResult::from_bool(
+                a.0 * inverse == QuaternionM::one() && inverse * a.0 == QuaternionM::one(),
+            ),
+        }
+    }
+
     #[quickcheck]
     fn prop_into_matrix_and_back(a: Q231) -> bool {
         let b: Matrix3<Q231> = a.into();
@@ -1,96 +1,178 @@
-use super::Invertible;
-use alga::general::Ring;
-use nalgebra::base::coordinates::IJKW;
-use nalgebra::{Vector3, Vector4};
+#[cfg(feature = "serde")]
+use super::matrix::Vector4;
+use super::{Invertible, Ring};
+use crate::error::Error;
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 use num_traits::{One, Zero};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use std::mem;
-use std::ops::{Add, AddAssign, Deref, DerefMut, Mul, MulAssign, Neg, Sub, SubAssign};
 
 /// Quaternion over a ring mod N
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
-pub(crate) struct QuaternionM<T>
+///
+/// Arithmetic is built solely from the ring operations of `T`, without
+/// branching on the coefficients, so it runs in constant time whenever
+/// those operations do.
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        from = "Coordinates<T>",
+        into = "Coordinates<T>",
+        bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>")
+    )
+)]
+pub struct QuaternionM<T>
 where
     T: Ring + fmt::Debug + Copy + Invertible + 'static,
 {
-    pub(crate) inner: Vector4<T>, // [x, y, z, w] or w + xi + yj + zk
+    /// Real part
+    pub w: T,
+    /// Coefficient of `i`
+    pub i: T,
+    /// Coefficient of `j`
+    pub j: T,
+    /// Coefficient of `k`
+    pub k: T,
+}
+
+/// Serialized form of `QuaternionM`, with the coordinates in the
+/// order `[i, j, k, w]`
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Ring"))]
+struct Coordinates<T> {
+    inner: Vector4<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> From<Coordinates<T>> for QuaternionM<T>
+where
+    T: Ring + fmt::Debug + Copy + Invertible + 'static,
+{
+    fn from(c: Coordinates<T>) -> Self {
+        Self::new(c.inner[3], c.inner[0], c.inner[1], c.inner[2])
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> From<QuaternionM<T>> for Coordinates<T>
+where
+    T: Ring + fmt::Debug + Copy + Invertible + 'static,
+{
+    fn from(q: QuaternionM<T>) -> Self {
+        Self {
+            inner: Vector4::new(q.i, q.j, q.k, q.w),
+        }
+    }
 }
 
 impl<T> QuaternionM<T>
 where
     T: Ring + fmt::Debug + Copy + Invertible + 'static,
 {
+    /// Creates the quaternion with real part `w` and coefficients `i`,
+    /// `j` and `k`
     #[inline]
     pub fn new(w: T, i: T, j: T, k: T) -> Self {
-        Self::from(Vector4::new(i, j, k, w))
+        Self { w, i, j, k }
     }
 
+    /// Creates the quaternion with real part `w` and imaginary part `ijk`
     #[inline]
-    pub fn from_parts(w: T, ijk: Vector3<T>) -> Self {
+    pub fn from_parts(w: T, ijk: [T; 3]) -> Self {
         Self::new(w, ijk[0], ijk[1], ijk[2])
     }
 
+    /// Creates the real quaternion `w`
     #[inline]
     pub fn from_real(w: T) -> Self {
-        Self::from_parts(w, Vector3::zero())
+        Self::new(w, T::zero(), T::zero(), T::zero())
     }
 
+    /// Creates the purely imaginary quaternion with coefficients `ijk`
     #[inline]
-    pub fn from_imag(ijk: Vector3<T>) -> Self {
+    pub fn from_imag(ijk: [T; 3]) -> Self {
         Self::from_parts(T::zero(), ijk)
     }
 
+    /// Coefficients of `i`, `j` and `k`
     #[inline]
-    pub fn imag(&self) -> Vector3<T> {
-        self.inner.xyz()
+    pub fn imag(&self) -> [T; 3] {
+        [self.i, self.j, self.k]
     }
 
+    /// Quaternion conjugate, which negates the imaginary part
     #[inline]
     pub fn conjugate(&self) -> Self {
-        Self::from_parts(self.w, self.imag().neg())
+        Self::new(self.w, -self.i, -self.j, -self.k)
     }
 
+    /// Norm of the quaternion, `w^2 + i^2 + j^2 + k^2`, which is
+    /// multiplicative
     #[inline]
     pub fn norm2(&self) -> T {
         (*self * self.conjugate()).w
     }
 
+    /// Multiplies by the scalar `w` on the left
     #[inline]
     pub fn scale(&self, w: T) -> Self {
         Self::from_real(w) * *self
     }
-}
 
-// impl<T> QuaternionM<T>
-// where
-//     T: Ring + fmt::Debug + Copy + Invertible + 'static,
-// {
-//     #[inline]
-//     pub fn recip(&self) -> Self {
-//         self.conjugate().scale(self.norm2().invert())
-//     }
-// }
+    /// Raises to the power `exp`
+    #[inline]
+    pub fn pow(&self, exp: u64) -> Self {
+        super::pow(*self, exp)
+    }
+}
 
-impl<T> Deref for QuaternionM<T>
+impl<T> QuaternionM<T>
 where
-    T: Ring + fmt::Debug + Copy + Invertible + 'static,
+    T: Ring + fmt::Debug + Copy + Invertible<Item = T> + 'static,
 {
-    type Target = IJKW<T>;
-
+    /// Right division `self * other^-1`, failing instead of panicking if
+    /// `other` is not invertible
     #[inline]
-    fn deref(&self) -> &Self::Target {
-        unsafe { mem::transmute(self) }
+    pub fn try_div(&self, other: &Self) -> Result<Self, Error> {
+        other
+            .try_invert()
+            .map(|inverse| *self * inverse)
+            .ok_or(Error::NotInvertible)
     }
 }
 
-impl<T> DerefMut for QuaternionM<T>
+impl<T> Invertible for QuaternionM<T>
 where
-    T: Ring + fmt::Debug + Copy + Invertible + 'static,
+    T: Ring + fmt::Debug + Copy + Invertible<Item = T> + 'static,
 {
+    type Item = Self;
+
+    /// The conjugate divided by the norm
+    ///
+    /// Over a finite field non-zero quaternions of norm zero exist, and
+    /// are not invertible.
+    fn try_invert(&self) -> Option<Self> {
+        let renorm = self.norm2().try_invert()?;
+        Some(self.conjugate().scale(renorm))
+    }
+}
+
+impl<T> Div for QuaternionM<T>
+where
+    T: Ring + fmt::Debug + Copy + Invertible<Item = T> + 'static,
+{
+    type Output = Self;
+
+    /// Right division `self * other^-1`
+    ///
+    /// Panics if `other` is not invertible, see `try_div`.
     #[inline]
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { mem::transmute(self) }
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, other: Self) -> Self {
+        self * other.invert()
     }
 }
 
@@ -112,21 +194,13 @@ where
     }
 }
 
-impl<T> From<Vector4<T>> for QuaternionM<T>
-where
-    T: Ring + fmt::Debug + Copy + Invertible + 'static,
-{
-    fn from(inner: Vector4<T>) -> Self {
-        Self { inner }
-    }
-}
-
 impl<T> From<[T; 4]> for QuaternionM<T>
 where
     T: Ring + fmt::Debug + Copy + Invertible + 'static,
 {
+    /// Creates the quaternion from coordinates in the order `[i, j, k, w]`
     fn from(arr: [T; 4]) -> Self {
-        Self::from(Vector4::from(arr))
+        Self::new(arr[3], arr[0], arr[1], arr[2])
     }
 }
 
@@ -138,7 +212,12 @@ where
 
     #[inline]
     fn add(self, other: Self) -> Self {
-        QuaternionM::from(self.inner + other.inner)
+        Self::new(
+            self.w + other.w,
+            self.i + other.i,
+            self.j + other.j,
+            self.k + other.k,
+        )
     }
 }
 
@@ -148,7 +227,7 @@ where
 {
     #[inline]
     fn add_assign(&mut self, other: Self) {
-        self.inner += other.inner
+        *self = *self + other
     }
 }
 
@@ -160,7 +239,12 @@ where
 
     #[inline]
     fn sub(self, other: Self) -> Self {
-        QuaternionM::from(self.inner - other.inner)
+        Self::new(
+            self.w - other.w,
+            self.i - other.i,
+            self.j - other.j,
+            self.k - other.k,
+        )
     }
 }
 
@@ -170,7 +254,7 @@ where
 {
     #[inline]
     fn sub_assign(&mut self, other: Self) {
-        self.inner -= other.inner
+        *self = *self - other
     }
 }
 
@@ -182,7 +266,7 @@ where
 
     #[inline]
     fn neg(self) -> Self {
-        self.inner.neg().into()
+        Self::new(-self.w, -self.i, -self.j, -self.k)
     }
 }
 
@@ -192,12 +276,12 @@ where
 {
     #[inline]
     fn zero() -> Self {
-        Self::from(Vector4::zero())
+        Self::from_real(T::zero())
     }
 
     #[inline]
     fn is_zero(&self) -> bool {
-        self.inner.is_zero()
+        self.w.is_zero() && self.i.is_zero() && self.j.is_zero() && self.k.is_zero()
     }
 }
 
@@ -208,11 +292,12 @@ where
     type Output = Self;
 
     fn mul(self, other: Self) -> Self::Output {
-        let s_ijk = self.imag();
-        let o_ijk = other.imag();
-        Self::from_parts(
-            self.w * other.w - s_ijk.dot(&o_ijk),
-            s_ijk.cross(&o_ijk) + (o_ijk * self.w) + s_ijk * other.w,
+        let (a, b) = (self, other);
+        Self::new(
+            a.w * b.w - a.i * b.i - a.j * b.j - a.k * b.k,
+            a.w * b.i + a.i * b.w + a.j * b.k - a.k * b.j,
+            a.w * b.j - a.i * b.k + a.j * b.w + a.k * b.i,
+            a.w * b.k + a.i * b.j - a.j * b.i + a.k * b.w,
         )
     }
 }
@@ -237,6 +322,6 @@ where
 
     #[inline]
     fn is_one(&self) -> bool {
-        self.w.is_one() && self.imag().is_zero()
+        self.w.is_one() && self.i.is_zero() && self.j.is_zero() && self.k.is_zero()
     }
 }
@@ -0,0 +1,286 @@
+//! ASCII-armored text form of ciphertexts and keys
+//!
+//! `Display` writes ciphertexts and keys as a typed prefix followed by
+//! unpadded URL-safe base64, and `FromStr` reads them back:
+//!
+//! ```text
+//! gmorph-enc1:Af___38D...   Enc
+//! gmorph-key1:Af___38D...   KeyPair
+//! ```
+//!
+//! The base64 holds the binary encoding described in the `wire` module,
+//! followed by a checksum: the first four bytes of its SHA-256 digest.
+//! The text contains no whitespace, quotes or commas, so it can be pasted
+//! into CSV cells and config files as it is. Surrounding whitespace is
+//! ignored when parsing.
+//!
+//! ## Example:
+//!
+//! ```
+//! use gmorph::*;
+//!
+//! let key_pair = KeyPair::default();
+//! let text = Enc::encrypt(&key_pair, 7).to_string();
+//! assert!(text.starts_with("gmorph-enc1:"));
+//!
+//! let enc: Enc = text.parse().unwrap();
+//! assert_eq!(7, enc.decrypt(&key_pair));
+//! assert!(text[1..].parse::<Enc>().is_err());
+//! ```
+use super::wire::{DecodeError, KEY_LEN};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::{DecodeError as Base64Error, DecodeSliceError, Engine};
+use core::fmt;
+use sha2::{Digest, Sha256};
+
+const CHECKSUM_LEN: usize = 4;
+/// Longest payload, that of a key, together with its checksum
+const MAX_BYTES: usize = KEY_LEN + CHECKSUM_LEN;
+const MAX_CHARS: usize = (MAX_BYTES * 4).div_ceil(3);
+
+/// Kind of value held by an armored text
+#[derive(Debug)]
+pub(crate) struct Kind {
+    prefix: &'static str,
+    name: &'static str,
+}
+
+pub(crate) const ENC: Kind = Kind {
+    prefix: "gmorph-enc1",
+    name: "ciphertext",
+};
+pub(crate) const KEY_PAIR: Kind = Kind {
+    prefix: "gmorph-key1",
+    name: "key pair",
+};
+const KINDS: [Kind; 2] = [ENC, KEY_PAIR];
+
+/// Errors raised when parsing armored text
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The text does not start with the expected prefix
+    Prefix { expected: &'static str },
+    /// The text holds a different kind of value than expected
+    Kind {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The base64 part holds an invalid character at the given byte
+    /// position of the text
+    Character { position: usize, character: char },
+    /// The base64 part is too long, too short or cut off in the middle
+    /// of a character
+    Length,
+    /// The checksum does not match, so the text was altered or cut short
+    Checksum,
+    /// The checksum matches, but the encoded value is invalid
+    Decode(DecodeError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Prefix { expected } => {
+                write!(f, "expected text starting with `{}:`", expected)
+            }
+            ParseError::Kind { expected, found } => {
+                write!(f, "expected a {}, but found a {}", expected, found)
+            }
+            ParseError::Character {
+                position,
+                character,
+            } => write!(
+                f,
+                "invalid character {:?} at position {}",
+                character, position
+            ),
+            ParseError::Length => write!(f, "armored text has the wrong length"),
+            ParseError::Checksum => {
+                write!(f, "checksum mismatch, the text was altered or cut short")
+            }
+            ParseError::Decode(e) => write!(f, "invalid encoding: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for ParseError {
+    #[inline]
+    fn from(e: DecodeError) -> Self {
+        ParseError::Decode(e)
+    }
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = Sha256::digest(bytes);
+    let mut out = [0u8; CHECKSUM_LEN];
+    out.copy_from_slice(&digest[..CHECKSUM_LEN]);
+    out
+}
+
+/// Writes `bytes` armored as `kind`
+pub(crate) fn write(f: &mut fmt::Formatter, kind: &Kind, bytes: &[u8]) -> fmt::Result {
+    let len = bytes.len() + CHECKSUM_LEN;
+    let mut payload = [0u8; MAX_BYTES];
+    payload[..bytes.len()].copy_from_slice(bytes);
+    payload[bytes.len()..len].copy_from_slice(&checksum(bytes));
+
+    let mut text = [0u8; MAX_CHARS];
+    let n = URL_SAFE_NO_PAD
+        .encode_slice(&payload[..len], &mut text)
+        .map_err(|_| fmt::Error)?;
+    // base64 is always ASCII
+    let text = core::str::from_utf8(&text[..n]).map_err(|_| fmt::Error)?;
+    write!(f, "{}:{}", kind.prefix, text)
+}
+
+/// Parses text armored as `kind`, decoding its payload with `decode`
+pub(crate) fn read<T, F>(s: &str, kind: &Kind, decode: F) -> Result<T, ParseError>
+where
+    F: FnOnce(&[u8]) -> Result<T, DecodeError>,
+{
+    let trimmed = s.trim();
+    let leading = s.len() - s.trim_start().len();
+    let body = match trimmed.split_once(':') {
+        Some((prefix, body)) if prefix == kind.prefix => body,
+        Some((prefix, _)) => {
+            return Err(match KINDS.iter().find(|k| k.prefix == prefix) {
+                Some(found) => ParseError::Kind {
+                    expected: kind.name,
+                    found: found.name,
+                },
+                None => ParseError::Prefix {
+                    expected: kind.prefix,
+                },
+            })
+        }
+        None => {
+            return Err(ParseError::Prefix {
+                expected: kind.prefix,
+            })
+        }
+    };
+
+    let invalid = |offset: usize| {
+        let mut position = leading + kind.prefix.len() + 1 + offset;
+        // the decoder counts bytes, which may land inside a multibyte character
+        while !s.is_char_boundary(position) {
+            position -= 1;
+        }
+        ParseError::Character {
+            position,
+            character: s[position..].chars().next().unwrap_or('\0'),
+        }
+    };
+    // room for the decoder to overshoot on inputs that are too long
+    let mut payload = [0u8; MAX_BYTES + 3];
+    let n = URL_SAFE_NO_PAD
+        .decode_slice(body, &mut payload)
+        .map_err(|e| match e {
+            DecodeSliceError::DecodeError(Base64Error::InvalidByte(offset, _))
+            | DecodeSliceError::DecodeError(Base64Error::InvalidLastSymbol(offset, _)) => {
+                invalid(offset)
+            }
+            _ => ParseError::Length,
+        })?;
+    if n < CHECKSUM_LEN {
+        return Err(ParseError::Length);
+    }
+    let (bytes, sum) = payload[..n].split_at(n - CHECKSUM_LEN);
+    if checksum(bytes) != sum {
+        return Err(ParseError::Checksum);
+    }
+    decode(bytes).map_err(ParseError::Decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enc::{Enc, Encrypt, KeyPair};
+    use crate::Decrypt;
+
+    #[quickcheck]
+    fn prop_enc_roundtrip(enc: Enc) -> bool {
+        let text = enc.to_string();
+        text.starts_with("gmorph-enc1:") && text.parse::<Enc>() == Ok(enc)
+    }
+
+    #[test]
+    fn keys_roundtrip() {
+        let key_pair = KeyPair::new();
+        let parsed: KeyPair = key_pair.to_string().parse().unwrap();
+        assert_eq!(parsed.fingerprint(), key_pair.fingerprint());
+    }
+
+    #[test]
+    fn errors_are_descriptive() {
+        let key_pair = KeyPair::new();
+        let text = Enc::encrypt(&key_pair, 1).to_string();
+
+        assert_eq!(
+            format!(" {} \n", text)
+                .parse::<Enc>()
+                .map(|x| x.decrypt(&key_pair)),
+            Ok(1)
+        );
+        assert_eq!(
+            "hello".parse::<Enc>(),
+            Err(ParseError::Prefix {
+                expected: "gmorph-enc1"
+            })
+        );
+        assert_eq!(
+            text.parse::<KeyPair>().unwrap_err(),
+            ParseError::Kind {
+                expected: "key pair",
+                found: "ciphertext"
+            }
+        );
+        assert_eq!(
+            format!("{}!{}", &text[..20], &text[21..]).parse::<Enc>(),
+            Err(ParseError::Character {
+                position: 20,
+                character: '!'
+            })
+        );
+        assert!(matches!(
+            "gmorph-key1:IFjCwl8DFw8DB0Sv-m5fVAWRhǌ69SfǼ".parse::<KeyPair>(),
+            Err(ParseError::Character { .. })
+        ));
+        assert_eq!(
+            text[..text.len() - 8].parse::<Enc>(),
+            Err(ParseError::Checksum)
+        );
+        assert_eq!("gmorph-enc1:AAA".parse::<Enc>(), Err(ParseError::Length));
+
+        // valid checksum over an invalid encoding
+        let mut bytes = Enc::encrypt(&key_pair, 1).to_bytes();
+        bytes[0] = 9;
+        let forged = Forged(&bytes).to_string();
+        assert_eq!(
+            forged.parse::<Enc>(),
+            Err(ParseError::Decode(DecodeError::Version(9)))
+        );
+        assert_eq!(
+            ParseError::Decode(DecodeError::Version(9)).to_string(),
+            "invalid encoding: unsupported format version 9"
+        );
+    }
+
+    struct Forged<'a>(&'a [u8]);
+
+    impl fmt::Display for Forged<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write(f, &ENC, self.0)
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Abstraction over homomorphic encryption schemes
+//!
+//! Code written against `HomomorphicBackend` instead of the concrete
+//! `Enc` and `KeyPair` types can be run either with the real `GMorph`
+//! backend, or with the `Plain` backend which performs the same modular
+//! arithmetic on unencrypted values. The latter is handy for unit tests,
+//! and as a reference when comparing against other schemes.
+use super::algebra::Mod231;
+use super::enc::{Decrypt, Enc, Encrypt, KeyPair};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Homomorphic encryption scheme over integers modulo 2^31 - 1
+///
+/// ## Example:
+///
+/// ```
+/// use gmorph::backend::{GMorph, HomomorphicBackend, Plain};
+///
+/// fn sum_of_squares<B: HomomorphicBackend>(key: &B::Key, xs: &[u32]) -> u32 {
+///     let sum = xs
+///         .iter()
+///         .map(|&x| B::encrypt(key, x))
+///         .fold(B::encrypt(key, 0), |acc, x| B::add(&acc, &B::mul(&x, &x)));
+///     B::decrypt(key, &sum)
+/// }
+///
+/// let xs = [1, 2, 3, 4];
+/// assert_eq!(30, sum_of_squares::<Plain>(&Plain::generate_key(), &xs));
+/// assert_eq!(30, sum_of_squares::<GMorph>(&GMorph::generate_key(), &xs));
+/// ```
+pub trait HomomorphicBackend {
+    /// Encrypted value
+    type Ciphertext: Clone;
+    /// Key used for both encryption and decryption
+    type Key;
+
+    /// Generates a new random key
+    fn generate_key() -> Self::Key;
+
+    /// Encrypts `value` using `key`
+    fn encrypt(key: &Self::Key, value: u32) -> Self::Ciphertext;
+
+    /// Decrypts `ciphertext` using `key`
+    fn decrypt(key: &Self::Key, ciphertext: &Self::Ciphertext) -> u32;
+
+    /// Homomorphic addition
+    fn add(lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext;
+
+    /// Homomorphic multiplication
+    fn mul(lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext;
+
+    /// Adds a plaintext constant
+    fn add_scalar(lhs: &Self::Ciphertext, rhs: u32) -> Self::Ciphertext;
+
+    /// Multiplies by a plaintext constant
+    fn mul_scalar(lhs: &Self::Ciphertext, rhs: u32) -> Self::Ciphertext;
+}
+
+/// The gMorph scheme, backed by `Enc` and `KeyPair`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GMorph;
+
+impl HomomorphicBackend for GMorph {
+    type Ciphertext = Enc;
+    type Key = KeyPair;
+
+    #[inline]
+    fn generate_key() -> Self::Key {
+        KeyPair::new()
+    }
+
+    #[inline]
+    fn encrypt(key: &Self::Key, value: u32) -> Self::Ciphertext {
+        Enc::encrypt(key, value)
+    }
+
+    #[inline]
+    fn decrypt(key: &Self::Key, ciphertext: &Self::Ciphertext) -> u32 {
+        ciphertext.decrypt(key)
+    }
+
+    #[inline]
+    fn add(lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext {
+        *lhs + *rhs
+    }
+
+    #[inline]
+    fn mul(lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext {
+        *lhs * *rhs
+    }
+
+    #[inline]
+    fn add_scalar(lhs: &Self::Ciphertext, rhs: u32) -> Self::Ciphertext {
+        *lhs + rhs
+    }
+
+    #[inline]
+    fn mul_scalar(lhs: &Self::Ciphertext, rhs: u32) -> Self::Ciphertext {
+        *lhs * rhs
+    }
+}
+
+/// Transparent scheme which keeps values in the clear
+///
+/// Arithmetic is carried out modulo 2^31 - 1 exactly as under `GMorph`,
+/// so both backends decrypt to the same results.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Plain;
+
+/// Unencrypted value used as the ciphertext of the `Plain` backend
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlainEnc(Mod231);
+
+impl HomomorphicBackend for Plain {
+    type Ciphertext = PlainEnc;
+    type Key = ();
+
+    #[inline]
+    fn generate_key() -> Self::Key {}
+
+    #[inline]
+    fn encrypt(_: &Self::Key, value: u32) -> Self::Ciphertext {
+        PlainEnc(Mod231::from(value))
+    }
+
+    #[inline]
+    fn decrypt(_: &Self::Key, ciphertext: &Self::Ciphertext) -> u32 {
+        (ciphertext.0).0
+    }
+
+    #[inline]
+    fn add(lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext {
+        PlainEnc(lhs.0 + rhs.0)
+    }
+
+    #[inline]
+    fn mul(lhs: &Self::Ciphertext, rhs: &Self::Ciphertext) -> Self::Ciphertext {
+        PlainEnc(lhs.0 * rhs.0)
+    }
+
+    #[inline]
+    fn add_scalar(lhs: &Self::Ciphertext, rhs: u32) -> Self::Ciphertext {
+        PlainEnc(lhs.0 + Mod231::from(rhs))
+    }
+
+    #[inline]
+    fn mul_scalar(lhs: &Self::Ciphertext, rhs: u32) -> Self::Ciphertext {
+        PlainEnc(lhs.0 * Mod231::from(rhs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval<B: HomomorphicBackend>(key: &B::Key, x: u32, y: u32) -> u32 {
+        let enc_x = B::encrypt(key, x);
+        let enc_y = B::encrypt(key, y);
+        let z = B::add(&B::mul(&enc_x, &enc_y), &B::mul_scalar(&enc_x, y));
+        B::decrypt(key, &B::add_scalar(&z, x))
+    }
+
+    #[quickcheck]
+    fn prop_backends_agree(x: u32, y: u32) -> bool {
+        let plain = eval::<Plain>(&Plain::generate_key(), x, y);
+        let gmorph = eval::<GMorph>(&GMorph::generate_key(), x, y);
+        plain == gmorph
+    }
+}
@@ -0,0 +1,329 @@
+//! Struct-of-arrays batches of ciphertexts
+//!
+//! An `EncBatch` stores many `Enc` values as 36 coefficient planes: plane
+//! `6 * r + c` holds coefficient `(r, c)` of every ciphertext, viewed as a
+//! 6x6 matrix over GF(2^31 - 1). Homomorphic operations on a batch then
+//! run the same arithmetic across whole planes, which maps onto SIMD
+//! lanes (see the `simd` module) instead of one ciphertext at a time.
+//!
+//! Encryption is not batched: every value draws its own noise and is
+//! masked with `Enc::encrypt` before being split into the planes, so
+//! `EncBatch::encrypt` costs the same as encrypting the values one by one.
+//!
+//! Decryption is linear in the coefficients, so a batch is decrypted by
+//! a single dot product per lane against a functional derived from the
+//! key once.
+//!
+//! ## Example:
+//!
+//! ```
+//! use gmorph::batch::EncBatch;
+//! use gmorph::*;
+//!
+//! let key_pair = KeyPair::default();
+//! let xs = EncBatch::encrypt(&key_pair, &[1, 2, 3]);
+//! let ys = EncBatch::encrypt(&key_pair, &[4, 5, 6]);
+//! let zs = &(&xs * &ys) + &xs;
+//!
+//! assert_eq!(zs.decrypt(&key_pair), vec![5, 12, 21]);
+//! assert_eq!(zs.sum().decrypt(&key_pair), 38);
+//! ```
+mod simd;
+
+use super::algebra::{normalize_u64, Mod231};
+#[cfg(feature = "std")]
+use super::enc::Encrypt;
+use super::enc::{Enc, KeyPair};
+use super::error::{check_length, Error};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+use core::ops::{Add, Mul};
+use rand::Rng;
+
+const PLANES: usize = 36;
+// lanes summed before reducing, small enough for 32-bit targets
+const SUM_CHUNK: usize = 1 << 30;
+
+/// Batch of ciphertexts stored as coefficient planes
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncBatch {
+    planes: Vec<Vec<u32>>,
+}
+
+impl EncBatch {
+    /// Creates an empty batch
+    pub fn new() -> Self {
+        Self::with_len(0)
+    }
+
+    fn with_len(len: usize) -> Self {
+        Self {
+            planes: vec![vec![0; len]; PLANES],
+        }
+    }
+
+    /// Encrypts every value in `values` using `key_pair`
+    ///
+    /// Each value is encrypted on its own with `Enc::encrypt`; only the
+    /// arithmetic on the resulting batch runs plane-wise.
+    #[cfg(feature = "std")]
+    pub fn encrypt(key_pair: &KeyPair, values: &[u32]) -> Self {
+        values
+            .iter()
+            .map(|&value| Enc::encrypt(key_pair, value))
+            .collect()
+    }
+
+    /// Encrypts every value in `values` using `key_pair`, drawing the
+    /// noise from `rng`
+    ///
+    /// Like `encrypt`, this encrypts one value at a time.
+    pub fn encrypt_with_rng<R: Rng + ?Sized>(
+        key_pair: &KeyPair,
+        values: &[u32],
+        rng: &mut R,
+    ) -> Self {
+        values
+            .iter()
+            .map(|&value| Enc::encrypt_with_rng(key_pair, value, rng))
+            .collect()
+    }
+
+    /// Decrypts every ciphertext in the batch using `key_pair`
+    pub fn decrypt(&self, key_pair: &KeyPair) -> Vec<u32> {
+        let functional = key_pair.decryption_functional();
+        let mut acc = vec![0; self.len()];
+        for (plane, l) in self.planes.iter().zip(functional.iter().flatten()) {
+            simd::scale_acc(&mut acc, plane, l.0);
+        }
+        let mut out = vec![0; self.len()];
+        simd::reduce(&mut out, &acc);
+        out
+    }
+
+    /// Number of ciphertexts in the batch
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.planes[0].len()
+    }
+
+    /// Returns `true` if the batch holds no ciphertexts
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends a ciphertext to the batch
+    pub fn push(&mut self, enc: Enc) {
+        for (plane, x) in self.planes.iter_mut().zip(enc.split().iter().flatten()) {
+            plane.push(x.0);
+        }
+    }
+
+    /// Returns the ciphertext at `index`, if any
+    pub fn get(&self, index: usize) -> Option<Enc> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut m = [[Mod231(0); 6]; 6];
+        for (x, plane) in m.iter_mut().flatten().zip(self.planes.iter()) {
+            *x = Mod231(plane[index]);
+        }
+        Some(Enc::join(&m))
+    }
+
+    /// Iterates over the ciphertexts in the batch
+    pub fn iter(&self) -> impl Iterator<Item = Enc> + '_ {
+        (0..self.len()).filter_map(move |index| self.get(index))
+    }
+
+    /// Homomorphic sum of all ciphertexts in the batch
+    pub fn sum(&self) -> Enc {
+        let mut m = [[Mod231(0); 6]; 6];
+        for (x, plane) in m.iter_mut().flatten().zip(self.planes.iter()) {
+            // each coefficient is below 2^31, so a u64 can take 2^33 of them
+            let total = plane
+                .chunks(SUM_CHUNK)
+                .map(|chunk| chunk.iter().map(|&c| u64::from(c)).sum::<u64>())
+                .fold(0, |acc, s| normalize_u64(u64::from(acc) + s));
+            *x = Mod231(total);
+        }
+        Enc::join(&m)
+    }
+
+    /// Like `&self + rhs`, but failing instead of panicking if the
+    /// batches differ in length
+    pub fn try_add(&self, rhs: &EncBatch) -> Result<EncBatch, Error> {
+        check_length(self.len(), rhs.len())?;
+        Ok(self + rhs)
+    }
+
+    /// Like `&self * rhs`, but failing instead of panicking if the
+    /// batches differ in length
+    pub fn try_mul(&self, rhs: &EncBatch) -> Result<EncBatch, Error> {
+        check_length(self.len(), rhs.len())?;
+        Ok(self * rhs)
+    }
+
+    #[inline]
+    fn plane(&self, r: usize, c: usize) -> &[u32] {
+        &self.planes[6 * r + c]
+    }
+}
+
+impl Default for EncBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<Enc> for EncBatch {
+    fn from_iter<I: IntoIterator<Item = Enc>>(iter: I) -> Self {
+        let mut batch = Self::new();
+        for enc in iter {
+            batch.push(enc);
+        }
+        batch
+    }
+}
+
+impl From<&[Enc]> for EncBatch {
+    fn from(encs: &[Enc]) -> Self {
+        encs.iter().copied().collect()
+    }
+}
+
+impl Add for &EncBatch {
+    type Output = EncBatch;
+
+    /// Adds the batches lane by lane
+    ///
+    /// Panics if the batches differ in length, see `EncBatch::try_add`.
+    fn add(self, rhs: Self) -> Self::Output {
+        assert_eq!(self.len(), rhs.len(), "sum of batches of different length");
+        let mut out = EncBatch::with_len(self.len());
+        for ((o, a), b) in out.planes.iter_mut().zip(&self.planes).zip(&rhs.planes) {
+            simd::add(o, a, b);
+        }
+        out
+    }
+}
+
+impl Add for EncBatch {
+    type Output = EncBatch;
+
+    /// Panics if the batches differ in length, see `EncBatch::try_add`
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        &self + &rhs
+    }
+}
+
+impl Mul for &EncBatch {
+    type Output = EncBatch;
+
+    /// Multiplies the batches lane by lane
+    ///
+    /// Panics if the batches differ in length, see `EncBatch::try_mul`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        assert_eq!(
+            self.len(),
+            rhs.len(),
+            "product of batches of different length"
+        );
+        let mut out = EncBatch::with_len(self.len());
+        let mut acc = vec![0; self.len()];
+        for r in 0..6 {
+            for c in 0..6 {
+                acc.iter_mut().for_each(|x| *x = 0);
+                for k in 0..6 {
+                    simd::mul_acc(&mut acc, self.plane(r, k), rhs.plane(k, c));
+                }
+                simd::reduce(&mut out.planes[6 * r + c], &acc);
+            }
+        }
+        out
+    }
+}
+
+impl Mul for EncBatch {
+    type Output = EncBatch;
+
+    /// Panics if the batches differ in length, see `EncBatch::try_mul`
+    #[inline]
+    fn mul(self, rhs: Self) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enc::Decrypt;
+    use num_traits::Zero;
+
+    #[quickcheck]
+    fn prop_roundtrip(encs: Vec<Enc>) -> bool {
+        EncBatch::from(&encs[..]).iter().eq(encs.into_iter())
+    }
+
+    #[quickcheck]
+    fn prop_add_matches_enc(pairs: Vec<(Enc, Enc)>) -> bool {
+        let xs: EncBatch = pairs.iter().map(|p| p.0).collect();
+        let ys: EncBatch = pairs.iter().map(|p| p.1).collect();
+        (&xs + &ys).iter().eq(pairs.iter().map(|&(x, y)| x + y))
+    }
+
+    #[quickcheck]
+    fn prop_mul_matches_enc(pairs: Vec<(Enc, Enc)>) -> bool {
+        let xs: EncBatch = pairs.iter().map(|p| p.0).collect();
+        let ys: EncBatch = pairs.iter().map(|p| p.1).collect();
+        (&xs * &ys).iter().eq(pairs.iter().map(|&(x, y)| x * y))
+    }
+
+    #[quickcheck]
+    fn prop_sum_matches_fold(encs: Vec<Enc>) -> bool {
+        let expected = encs.iter().fold(Enc::zero(), |acc, &x| acc + x);
+        EncBatch::from(&encs[..]).sum() == expected
+    }
+
+    #[quickcheck]
+    fn prop_decrypt_matches_enc(values: Vec<u32>) -> bool {
+        let key_pair = KeyPair::new();
+        let batch = EncBatch::encrypt(&key_pair, &values);
+        let expected: Vec<u32> = batch.iter().map(|x| x.decrypt(&key_pair)).collect();
+        batch.decrypt(&key_pair) == expected
+            && expected
+                .iter()
+                .zip(&values)
+                .all(|(&x, &v)| Mod231::from(v) == x)
+    }
+
+    #[test]
+    fn try_ops_check_length() {
+        let key_pair = KeyPair::new();
+        let xs = EncBatch::encrypt(&key_pair, &[2, 3]);
+        let ys = EncBatch::encrypt(&key_pair, &[4]);
+        assert_eq!(xs.try_add(&xs).unwrap().decrypt(&key_pair), vec![4, 6]);
+        assert_eq!(xs.try_mul(&xs).unwrap().decrypt(&key_pair), vec![4, 9]);
+        assert!(matches!(
+            xs.try_add(&ys),
+            Err(Error::Length {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(ys.try_mul(&xs), Err(Error::Length { .. })));
+    }
+
+    #[test]
+    fn empty_batch() {
+        let key_pair = KeyPair::new();
+        let batch = EncBatch::new();
+        assert!(batch.is_empty());
+        assert_eq!(batch.get(0), None);
+        assert!(batch.decrypt(&key_pair).is_empty());
+        assert_eq!(batch.sum().decrypt(&key_pair), 0);
+    }
+}
@@ -0,0 +1,296 @@
+//! Lane-wise arithmetic on coefficient planes
+//!
+//! Every kernel comes in a portable version, written as a plain loop the
+//! compiler can auto-vectorize, and in explicit SSE4.1 and AVX2 versions
+//! selected on x86 at runtime, or at compile time without `std`.
+//! Coefficients are reduced `u32`s; products are accumulated in `u64`
+//! lanes after folding each one below 2^32, so an accumulator can take
+//! 2^32 products before it overflows.
+use crate::algebra::{normalize_u64, MODULUS};
+
+const MODULUSU64: u64 = MODULUS as u64;
+
+#[inline]
+fn fold(v: u64) -> u64 {
+    (v >> 31) + (v & MODULUSU64)
+}
+
+/// Whether AVX2 is available, detected at runtime with `std` and at
+/// compile time without
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+fn has_avx2() -> bool {
+    #[cfg(feature = "std")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        cfg!(target_feature = "avx2")
+    }
+}
+
+/// Whether SSE4.1 is available, detected like `has_avx2`
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+fn has_sse41() -> bool {
+    #[cfg(feature = "std")]
+    {
+        is_x86_feature_detected!("sse4.1")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        cfg!(target_feature = "sse4.1")
+    }
+}
+
+/// `out[n] = a[n] + b[n]`
+pub(super) fn add(out: &mut [u32], a: &[u32], b: &[u32]) {
+    assert!(a.len() == out.len() && b.len() == out.len());
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if has_avx2() {
+            return unsafe { x86::add_avx2(out, a, b) };
+        }
+        if has_sse41() {
+            return unsafe { x86::add_sse41(out, a, b) };
+        }
+    }
+    add_portable(out, a, b)
+}
+
+/// `acc[n] += a[n] * b[n]`, with each product folded below 2^32
+pub(super) fn mul_acc(acc: &mut [u64], a: &[u32], b: &[u32]) {
+    assert!(a.len() == acc.len() && b.len() == acc.len());
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if has_avx2() {
+            return unsafe { x86::mul_acc_avx2(acc, a, b) };
+        }
+        if has_sse41() {
+            return unsafe { x86::mul_acc_sse41(acc, a, b) };
+        }
+    }
+    mul_acc_portable(acc, a, b)
+}
+
+/// `acc[n] += a[n] * s`, with each product folded below 2^32
+pub(super) fn scale_acc(acc: &mut [u64], a: &[u32], s: u32) {
+    assert!(a.len() == acc.len());
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if has_avx2() {
+            return unsafe { x86::scale_acc_avx2(acc, a, s) };
+        }
+        if has_sse41() {
+            return unsafe { x86::scale_acc_sse41(acc, a, s) };
+        }
+    }
+    scale_acc_portable(acc, a, s)
+}
+
+/// `out[n] = acc[n] mod 2^31 - 1`
+pub(super) fn reduce(out: &mut [u32], acc: &[u64]) {
+    for (o, &v) in out.iter_mut().zip(acc) {
+        *o = normalize_u64(v);
+    }
+}
+
+fn add_portable(out: &mut [u32], a: &[u32], b: &[u32]) {
+    for ((o, &x), &y) in out.iter_mut().zip(a).zip(b) {
+        let s = x + y;
+        // if s < MODULUS the subtraction wraps and min picks s
+        *o = s.min(s.wrapping_sub(MODULUS));
+    }
+}
+
+fn mul_acc_portable(acc: &mut [u64], a: &[u32], b: &[u32]) {
+    for ((o, &x), &y) in acc.iter_mut().zip(a).zip(b) {
+        *o += fold(u64::from(x) * u64::from(y));
+    }
+}
+
+fn scale_acc_portable(acc: &mut [u64], a: &[u32], s: u32) {
+    let s = u64::from(s);
+    for (o, &x) in acc.iter_mut().zip(a) {
+        *o += fold(u64::from(x) * s);
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    //! All functions require slices of equal length, which the
+    //! dispatching functions assert.
+    use super::MODULUS;
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn add_avx2(out: &mut [u32], a: &[u32], b: &[u32]) {
+        let p = _mm256_set1_epi32(MODULUS as i32);
+        let done = out.len() / 8 * 8;
+        for i in (0..done).step_by(8) {
+            let x = _mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i);
+            let y = _mm256_loadu_si256(b.as_ptr().add(i) as *const __m256i);
+            let s = _mm256_add_epi32(x, y);
+            let r = _mm256_min_epu32(s, _mm256_sub_epi32(s, p));
+            _mm256_storeu_si256(out.as_mut_ptr().add(i) as *mut __m256i, r);
+        }
+        super::add_portable(&mut out[done..], &a[done..], &b[done..]);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub(super) unsafe fn add_sse41(out: &mut [u32], a: &[u32], b: &[u32]) {
+        let p = _mm_set1_epi32(MODULUS as i32);
+        let done = out.len() / 4 * 4;
+        for i in (0..done).step_by(4) {
+            let x = _mm_loadu_si128(a.as_ptr().add(i) as *const __m128i);
+            let y = _mm_loadu_si128(b.as_ptr().add(i) as *const __m128i);
+            let s = _mm_add_epi32(x, y);
+            let r = _mm_min_epu32(s, _mm_sub_epi32(s, p));
+            _mm_storeu_si128(out.as_mut_ptr().add(i) as *mut __m128i, r);
+        }
+        super::add_portable(&mut out[done..], &a[done..], &b[done..]);
+    }
+
+    /// Folds four 62-bit products below 2^32 and adds them to `dst`
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn fold_acc_avx2(dst: *mut __m256i, v: __m256i) {
+        let p = _mm256_set1_epi64x(i64::from(MODULUS));
+        let folded = _mm256_add_epi64(_mm256_srli_epi64(v, 31), _mm256_and_si256(v, p));
+        _mm256_storeu_si256(dst, _mm256_add_epi64(_mm256_loadu_si256(dst), folded));
+    }
+
+    /// Folds two 62-bit products below 2^32 and adds them to `dst`
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn fold_acc_sse41(dst: *mut __m128i, v: __m128i) {
+        let p = _mm_set1_epi64x(i64::from(MODULUS));
+        let folded = _mm_add_epi64(_mm_srli_epi64(v, 31), _mm_and_si128(v, p));
+        _mm_storeu_si128(dst, _mm_add_epi64(_mm_loadu_si128(dst), folded));
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn mul_acc_avx2(acc: &mut [u64], a: &[u32], b: &[u32]) {
+        let done = acc.len() / 4 * 4;
+        for i in (0..done).step_by(4) {
+            let x = _mm256_cvtepu32_epi64(_mm_loadu_si128(a.as_ptr().add(i) as *const __m128i));
+            let y = _mm256_cvtepu32_epi64(_mm_loadu_si128(b.as_ptr().add(i) as *const __m128i));
+            let dst = acc.as_mut_ptr().add(i) as *mut __m256i;
+            fold_acc_avx2(dst, _mm256_mul_epu32(x, y));
+        }
+        super::mul_acc_portable(&mut acc[done..], &a[done..], &b[done..]);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub(super) unsafe fn mul_acc_sse41(acc: &mut [u64], a: &[u32], b: &[u32]) {
+        let done = acc.len() / 2 * 2;
+        for i in (0..done).step_by(2) {
+            let x = _mm_cvtepu32_epi64(_mm_loadl_epi64(a.as_ptr().add(i) as *const __m128i));
+            let y = _mm_cvtepu32_epi64(_mm_loadl_epi64(b.as_ptr().add(i) as *const __m128i));
+            let dst = acc.as_mut_ptr().add(i) as *mut __m128i;
+            fold_acc_sse41(dst, _mm_mul_epu32(x, y));
+        }
+        super::mul_acc_portable(&mut acc[done..], &a[done..], &b[done..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn scale_acc_avx2(acc: &mut [u64], a: &[u32], s: u32) {
+        let y = _mm256_set1_epi64x(i64::from(s));
+        let done = acc.len() / 4 * 4;
+        for i in (0..done).step_by(4) {
+            let x = _mm256_cvtepu32_epi64(_mm_loadu_si128(a.as_ptr().add(i) as *const __m128i));
+            let dst = acc.as_mut_ptr().add(i) as *mut __m256i;
+            fold_acc_avx2(dst, _mm256_mul_epu32(x, y));
+        }
+        super::scale_acc_portable(&mut acc[done..], &a[done..], s);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub(super) unsafe fn scale_acc_sse41(acc: &mut [u64], a: &[u32], s: u32) {
+        let y = _mm_set1_epi64x(i64::from(s));
+        let done = acc.len() / 2 * 2;
+        for i in (0..done).step_by(2) {
+            let x = _mm_cvtepu32_epi64(_mm_loadl_epi64(a.as_ptr().add(i) as *const __m128i));
+            let dst = acc.as_mut_ptr().add(i) as *mut __m128i;
+            fold_acc_sse41(dst, _mm_mul_epu32(x, y));
+        }
+        super::scale_acc_portable(&mut acc[done..], &a[done..], s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::Mod231;
+
+    fn planes(a: Vec<Mod231>, b: Vec<Mod231>) -> (Vec<u32>, Vec<u32>) {
+        let len = a.len().min(b.len());
+        (
+            a[..len].iter().map(|x| x.0).collect(),
+            b[..len].iter().map(|x| x.0).collect(),
+        )
+    }
+
+    #[quickcheck]
+    fn prop_add_matches_scalar(a: Vec<Mod231>, b: Vec<Mod231>) -> bool {
+        let (a, b) = planes(a, b);
+        let mut expected = vec![0; a.len()];
+        let mut out = vec![0; a.len()];
+        add_portable(&mut expected, &a, &b);
+        add(&mut out, &a, &b);
+        out == expected
+            && out
+                .iter()
+                .zip(a.iter().zip(&b))
+                .all(|(&o, (&x, &y))| Mod231(o) == Mod231(x) + Mod231(y))
+    }
+
+    #[quickcheck]
+    fn prop_mul_acc_matches_scalar(a: Vec<Mod231>, b: Vec<Mod231>, s: Mod231) -> bool {
+        let (a, b) = planes(a, b);
+        let mut acc = vec![0; a.len()];
+        mul_acc(&mut acc, &a, &b);
+        scale_acc(&mut acc, &a, s.0);
+        let mut out = vec![0; a.len()];
+        reduce(&mut out, &acc);
+        out.iter()
+            .zip(a.iter().zip(&b))
+            .all(|(&o, (&x, &y))| Mod231(o) == Mod231(x) * Mod231(y) + Mod231(x) * s)
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[quickcheck]
+    fn prop_x86_paths_match_portable(a: Vec<Mod231>, b: Vec<Mod231>, s: Mod231) -> bool {
+        let (a, b) = planes(a, b);
+        let len = a.len();
+        let mut expected = (vec![0; len], vec![0; len]);
+        add_portable(&mut expected.0, &a, &b);
+        mul_acc_portable(&mut expected.1, &a, &b);
+        scale_acc_portable(&mut expected.1, &a, s.0);
+
+        let mut paths = Vec::new();
+        if has_sse41() {
+            let mut out = (vec![0; len], vec![0; len]);
+            unsafe {
+                x86::add_sse41(&mut out.0, &a, &b);
+                x86::mul_acc_sse41(&mut out.1, &a, &b);
+                x86::scale_acc_sse41(&mut out.1, &a, s.0);
+            }
+            paths.push(out);
+        }
+        if has_avx2() {
+            let mut out = (vec![0; len], vec![0; len]);
+            unsafe {
+                x86::add_avx2(&mut out.0, &a, &b);
+                x86::mul_acc_avx2(&mut out.1, &a, &b);
+                x86::scale_acc_avx2(&mut out.1, &a, s.0);
+            }
+            paths.push(out);
+        }
+        paths.into_iter().all(|out| out == expected)
+    }
+}
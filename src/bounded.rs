@@ -0,0 +1,369 @@
+//! Plaintext magnitude tracking for `Enc` computations
+//!
+//! All arithmetic on `Enc` is carried out modulo 2^31 - 1, so sums and
+//! products which outgrow the modulus wrap around without any trace in the
+//! ciphertext. `Bounded` pairs an `Enc` with a public upper bound on the
+//! magnitude of its plaintext, derived only from the declared input ranges,
+//! and refuses to decrypt once that bound has left the representable range.
+use super::algebra::MODULUS;
+#[cfg(feature = "std")]
+use super::enc::Encrypt;
+use super::enc::{Decrypt, Enc, KeyPair};
+use core::fmt;
+use core::ops::{Add, AddAssign, Mul, MulAssign};
+
+/// Range in which the plaintext of a `Bounded` value has to stay
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Limit {
+    /// Plaintexts are unsigned and must stay below the modulus
+    Modulus,
+    /// Plaintexts are signed and their absolute value must not
+    /// exceed `(MODULUS - 1) / 2`
+    Signed,
+}
+
+impl Limit {
+    /// Largest magnitude allowed by this limit
+    #[inline]
+    pub fn max(self) -> u32 {
+        match self {
+            Limit::Modulus => MODULUS - 1,
+            Limit::Signed => (MODULUS - 1) / 2,
+        }
+    }
+
+    #[inline]
+    fn stricter(self, other: Self) -> Self {
+        if self.max() <= other.max() {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Modulus => write!(f, "unsigned range"),
+            Limit::Signed => write!(f, "safe signed range"),
+        }
+    }
+}
+
+/// Errors raised by magnitude tracking
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BoundError {
+    /// The tracked bound has grown past the limit, so the decrypted
+    /// value may have wrapped around the modulus
+    Overflow { bound: u128, limit: Limit },
+    /// A value handed in for encryption lies outside its declared range
+    OutOfRange { value: u64, max: u32 },
+}
+
+impl fmt::Display for BoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoundError::Overflow { bound, limit } => write!(
+                f,
+                "plaintext bound {} exceeds the {} (max {})",
+                bound,
+                limit,
+                limit.max()
+            ),
+            BoundError::OutOfRange { value, max } => write!(
+                f,
+                "value {} lies outside the declared range (max {})",
+                value, max
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BoundError {}
+
+/// `Enc` together with a public upper bound on its plaintext magnitude
+///
+/// The bound is propagated through `+`, `*` and scalar operations. Operators
+/// never fail; instead the overflow is reported by `check` and by `decrypt`.
+/// Use `checked_add` and `checked_mul` to fail as early as possible.
+///
+/// ## Example:
+///
+/// ```
+/// use gmorph::*;
+///
+/// let key_pair = KeyPair::default();
+/// let x = Bounded::encrypt(&key_pair, 40_000, 40_000).unwrap();
+/// let y = Bounded::encrypt(&key_pair, 30_000, 40_000).unwrap();
+///
+/// assert_eq!(1_200_000_000, (x * y).decrypt(&key_pair).unwrap());
+/// assert!((x * y * 2).decrypt(&key_pair).is_err());
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Bounded {
+    inner: Enc,
+    bound: u128,
+    limit: Limit,
+}
+
+impl Bounded {
+    /// Wraps `inner` whose plaintext is declared to lie in `0..=max`
+    #[inline]
+    pub fn new(inner: Enc, max: u32) -> Result<Self, BoundError> {
+        Self::with_limit(inner, max, Limit::Modulus)
+    }
+
+    /// Wraps `inner` whose plaintext magnitude is declared not to exceed
+    /// `max`, and which is checked against `limit`
+    #[inline]
+    pub fn with_limit(inner: Enc, max: u32, limit: Limit) -> Result<Self, BoundError> {
+        Self {
+            inner,
+            bound: max.into(),
+            limit,
+        }
+        .check()
+    }
+
+    /// Encrypts `value` declared to lie in `0..=max`
+    #[cfg(feature = "std")]
+    pub fn encrypt(key_pair: &KeyPair, value: u32, max: u32) -> Result<Self, BoundError> {
+        if value > max {
+            return Err(BoundError::OutOfRange {
+                value: value.into(),
+                max,
+            });
+        }
+        Self::new(Enc::encrypt(key_pair, value), max)
+    }
+
+    /// Encrypts signed `value` declared to lie in `-max..=max`
+    ///
+    /// Negative values are stored as their additive inverses modulo
+    /// `MODULUS`, and the result is checked against `Limit::Signed`.
+    #[cfg(feature = "std")]
+    pub fn encrypt_signed(key_pair: &KeyPair, value: i32, max: u32) -> Result<Self, BoundError> {
+        let magnitude = value.unsigned_abs();
+        if magnitude > max {
+            return Err(BoundError::OutOfRange {
+                value: magnitude.into(),
+                max,
+            });
+        }
+        let enc = Enc::encrypt(key_pair, magnitude);
+        let enc = if value < 0 { enc * (MODULUS - 1) } else { enc };
+        Self::with_limit(enc, max, Limit::Signed)
+    }
+
+    /// Public upper bound on the plaintext magnitude
+    #[inline]
+    pub fn bound(&self) -> u128 {
+        self.bound
+    }
+
+    /// Range the plaintext has to stay in
+    #[inline]
+    pub fn limit(&self) -> Limit {
+        self.limit
+    }
+
+    /// Returns `true` if the bound still fits in the limit
+    #[inline]
+    pub fn is_within_limit(&self) -> bool {
+        self.bound <= u128::from(self.limit.max())
+    }
+
+    /// Returns `self` if the bound still fits in the limit, and an
+    /// `Overflow` error otherwise
+    #[inline]
+    pub fn check(self) -> Result<Self, BoundError> {
+        if self.is_within_limit() {
+            Ok(self)
+        } else {
+            Err(BoundError::Overflow {
+                bound: self.bound,
+                limit: self.limit,
+            })
+        }
+    }
+
+    /// Adds `rhs`, failing if the result may wrap around
+    #[inline]
+    pub fn checked_add(self, rhs: Self) -> Result<Self, BoundError> {
+        (self + rhs).check()
+    }
+
+    /// Multiplies by `rhs`, failing if the result may wrap around
+    #[inline]
+    pub fn checked_mul(self, rhs: Self) -> Result<Self, BoundError> {
+        (self * rhs).check()
+    }
+
+    /// Unwraps the underlying `Enc`, dropping the bound
+    #[inline]
+    pub fn into_inner(self) -> Enc {
+        self.inner
+    }
+
+    /// Decrypts the plaintext, provided it cannot have wrapped around
+    pub fn decrypt(&self, key_pair: &KeyPair) -> Result<u32, BoundError> {
+        let value = self.check()?.inner.decrypt(key_pair);
+        if self.limit == Limit::Signed && value > self.limit.max() {
+            // Negative plaintexts have to go through `decrypt_signed`
+            return Err(BoundError::OutOfRange {
+                value: value.into(),
+                max: self.limit.max(),
+            });
+        }
+        Ok(value)
+    }
+
+    /// Decrypts a signed plaintext, provided it cannot have wrapped around
+    pub fn decrypt_signed(&self, key_pair: &KeyPair) -> Result<i32, BoundError> {
+        let value = self.check()?.inner.decrypt(key_pair);
+        if value > Limit::Signed.max() {
+            Ok(-((MODULUS - value) as i32))
+        } else {
+            Ok(value as i32)
+        }
+    }
+}
+
+impl Add for Bounded {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            inner: self.inner + rhs.inner,
+            bound: self.bound.saturating_add(rhs.bound),
+            limit: self.limit.stricter(rhs.limit),
+        }
+    }
+}
+
+impl AddAssign for Bounded {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs
+    }
+}
+
+impl Mul for Bounded {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            inner: self.inner * rhs.inner,
+            bound: self.bound.saturating_mul(rhs.bound),
+            limit: self.limit.stricter(rhs.limit),
+        }
+    }
+}
+
+impl MulAssign for Bounded {
+    #[inline]
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs
+    }
+}
+
+impl Add<u32> for Bounded {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: u32) -> Self::Output {
+        Self {
+            inner: self.inner + rhs,
+            bound: self.bound.saturating_add(rhs.into()),
+            limit: self.limit,
+        }
+    }
+}
+
+impl AddAssign<u32> for Bounded {
+    #[inline]
+    fn add_assign(&mut self, rhs: u32) {
+        *self = *self + rhs
+    }
+}
+
+impl Mul<u32> for Bounded {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: u32) -> Self::Output {
+        Self {
+            inner: self.inner * rhs,
+            bound: self.bound.saturating_mul(rhs.into()),
+            limit: self.limit,
+        }
+    }
+}
+
+impl MulAssign<u32> for Bounded {
+    #[inline]
+    fn mul_assign(&mut self, rhs: u32) {
+        *self = *self * rhs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[quickcheck]
+    fn prop_bounded_add_within_limit(x: u32, y: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let max = Limit::Modulus.max() / 2;
+        let (x, y) = (x % max, y % max);
+        let enc_x = Bounded::encrypt(&key_pair, x, max).unwrap();
+        let enc_y = Bounded::encrypt(&key_pair, y, max).unwrap();
+        (enc_x + enc_y).decrypt(&key_pair) == Ok(x + y)
+    }
+
+    #[quickcheck]
+    fn prop_bounded_signed_roundtrip(x: i32, y: i32) -> bool {
+        let key_pair = KeyPair::new();
+        let (x, y) = (x % 10_000, y % 10_000);
+        let enc_x = Bounded::encrypt_signed(&key_pair, x, 10_000).unwrap();
+        let enc_y = Bounded::encrypt_signed(&key_pair, y, 10_000).unwrap();
+        (enc_x * enc_y + enc_x).decrypt_signed(&key_pair) == Ok(x * y + x)
+    }
+
+    #[test]
+    fn mul_overflow_is_reported() {
+        let key_pair = KeyPair::new();
+        let x = Bounded::encrypt(&key_pair, 3, 1 << 15).unwrap();
+        assert_eq!(9, (x * x).decrypt(&key_pair).unwrap());
+        assert_eq!(
+            Err(BoundError::Overflow {
+                bound: 1 << 45,
+                limit: Limit::Modulus
+            }),
+            (x * x * x).decrypt(&key_pair)
+        );
+        assert!(x.checked_mul(x).unwrap().checked_mul(x).is_err());
+    }
+
+    #[test]
+    fn signed_limit_is_stricter() {
+        let key_pair = KeyPair::new();
+        let x = Bounded::encrypt(&key_pair, 1, 1 << 30).unwrap();
+        assert!(x.check().is_ok());
+        let y = Bounded::encrypt_signed(&key_pair, -1, 1 << 30);
+        assert!(y.is_err());
+    }
+
+    #[test]
+    fn out_of_range_is_rejected() {
+        let key_pair = KeyPair::new();
+        assert_eq!(
+            Err(BoundError::OutOfRange { value: 11, max: 10 }),
+            Bounded::encrypt(&key_pair, 11, 10).map(|x| x.bound())
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Operation counting and cost estimation for `Enc` computations
+//!
+//! Every `Enc` multiplication is a product of two 3x3 quaternion matrices,
+//! so the cost of a computation is dominated by how many of those it
+//! performs. `Meter` hands out `Metered` values which record each
+//! operation they take part in, together with the multiplicative depth of
+//! every intermediate value. Running the computation on `Meter::dummy`
+//! values gives a dry run which needs no keys, and whose counts can be
+//! turned into an `Estimate` with a `CostModel`.
+use super::enc::{Decrypt, Enc, Encrypt, KeyPair};
+use num_traits::Zero;
+use std::cell::Cell;
+use std::hint;
+use std::mem;
+use std::ops::{Add, Mul};
+use std::time::{Duration, Instant};
+
+/// Number of operations recorded by a `Meter`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Counts {
+    /// `Enc + Enc` operations
+    pub additions: u64,
+    /// `Enc * Enc` operations
+    pub multiplications: u64,
+    /// `Enc + u32` operations
+    pub scalar_additions: u64,
+    /// `Enc * u32` operations
+    pub scalar_multiplications: u64,
+    /// Largest multiplicative depth of any value
+    pub depth: u32,
+}
+
+impl Counts {
+    /// Number of bytes read and written by the recorded operations
+    ///
+    /// Binary operations read two ciphertexts and write one, scalar
+    /// operations read and write one ciphertext each.
+    pub fn bytes_moved(&self) -> u64 {
+        let size = mem::size_of::<Enc>() as u64;
+        let binary = self.additions + self.multiplications;
+        let scalar = self.scalar_additions + self.scalar_multiplications;
+        size * (3 * binary + 2 * scalar)
+    }
+}
+
+/// Shared operation counter
+///
+/// ## Example:
+///
+/// ```
+/// use gmorph::cost::{CostModel, Meter};
+///
+/// let meter = Meter::new();
+/// let x = meter.dummy();
+/// let y = (x * x + x) * x;
+///
+/// assert_eq!(2, y.depth());
+/// assert_eq!(2, meter.counts().multiplications);
+///
+/// let estimate = meter.estimate(&CostModel::calibrate(10));
+/// println!("{:?}", estimate.time);
+/// ```
+#[derive(Debug, Default)]
+pub struct Meter {
+    counts: Cell<Counts>,
+}
+
+impl Meter {
+    /// Creates a meter with all counts set to zero
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking `inner` as a fresh input of depth zero
+    #[inline]
+    pub fn wrap(&self, inner: Enc) -> Metered<'_> {
+        Metered {
+            inner,
+            depth: 0,
+            meter: self,
+        }
+    }
+
+    /// Encrypts `value` and starts tracking it
+    #[inline]
+    pub fn encrypt(&self, key_pair: &KeyPair, value: u32) -> Metered<'_> {
+        self.wrap(Enc::encrypt(key_pair, value))
+    }
+
+    /// Returns an input which requires no key, for dry runs
+    #[inline]
+    pub fn dummy(&self) -> Metered<'_> {
+        self.wrap(Enc::zero())
+    }
+
+    /// Operations recorded so far
+    #[inline]
+    pub fn counts(&self) -> Counts {
+        self.counts.get()
+    }
+
+    /// Sets all counts back to zero
+    #[inline]
+    pub fn reset(&self) {
+        self.counts.set(Counts::default())
+    }
+
+    /// Estimates the cost of the recorded operations under `model`
+    pub fn estimate(&self, model: &CostModel) -> Estimate {
+        let counts = self.counts();
+        Estimate {
+            counts,
+            bytes_moved: counts.bytes_moved(),
+            time: model.time(&counts),
+        }
+    }
+
+    fn record(&self, op: Op, depth: u32) {
+        let mut counts = self.counts.get();
+        match op {
+            Op::Add => counts.additions += 1,
+            Op::Mul => counts.multiplications += 1,
+            Op::ScalarAdd => counts.scalar_additions += 1,
+            Op::ScalarMul => counts.scalar_multiplications += 1,
+        }
+        counts.depth = counts.depth.max(depth);
+        self.counts.set(counts);
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Op {
+    Add,
+    Mul,
+    ScalarAdd,
+    ScalarMul,
+}
+
+/// `Enc` value whose operations are recorded by a `Meter`
+#[derive(Debug, Copy, Clone)]
+pub struct Metered<'a> {
+    inner: Enc,
+    depth: u32,
+    meter: &'a Meter,
+}
+
+impl<'a> Metered<'a> {
+    /// Multiplicative depth of this value
+    #[inline]
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Unwraps the underlying `Enc`
+    #[inline]
+    pub fn into_inner(self) -> Enc {
+        self.inner
+    }
+
+    #[inline]
+    fn with(self, inner: Enc, depth: u32) -> Self {
+        Self {
+            inner,
+            depth,
+            meter: self.meter,
+        }
+    }
+}
+
+impl<'a> Decrypt for Metered<'a> {
+    type Output = u32;
+
+    #[inline]
+    fn decrypt(&self, key_pair: &KeyPair) -> Self::Output {
+        self.inner.decrypt(key_pair)
+    }
+}
+
+impl<'a> Add for Metered<'a> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        let depth = self.depth.max(rhs.depth);
+        self.meter.record(Op::Add, depth);
+        self.with(self.inner + rhs.inner, depth)
+    }
+}
+
+impl<'a> Mul for Metered<'a> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self::Output {
+        let depth = self.depth.max(rhs.depth) + 1;
+        self.meter.record(Op::Mul, depth);
+        self.with(self.inner * rhs.inner, depth)
+    }
+}
+
+impl<'a> Add<u32> for Metered<'a> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: u32) -> Self::Output {
+        self.meter.record(Op::ScalarAdd, self.depth);
+        self.with(self.inner + rhs, self.depth)
+    }
+}
+
+impl<'a> Mul<u32> for Metered<'a> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: u32) -> Self::Output {
+        self.meter.record(Op::ScalarMul, self.depth);
+        self.with(self.inner * rhs, self.depth)
+    }
+}
+
+/// Time taken by each kind of `Enc` operation on a given machine
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CostModel {
+    pub addition: Duration,
+    pub multiplication: Duration,
+    pub scalar_addition: Duration,
+    pub scalar_multiplication: Duration,
+}
+
+impl CostModel {
+    /// Measures the cost of each operation on this machine, averaging
+    /// over `iterations` runs
+    pub fn calibrate(iterations: u32) -> Self {
+        let iterations = iterations.max(1);
+        let key_pair = KeyPair::new();
+        let x = Enc::encrypt(&key_pair, 3);
+        let y = Enc::encrypt(&key_pair, 5);
+
+        Self {
+            addition: measure(iterations, x, |acc| acc + y),
+            multiplication: measure(iterations, x, |acc| acc * y),
+            scalar_addition: measure(iterations, x, |acc| acc + 7),
+            scalar_multiplication: measure(iterations, x, |acc| acc * 7),
+        }
+    }
+
+    /// Estimated time needed to perform `counts` operations
+    pub fn time(&self, counts: &Counts) -> Duration {
+        let total = self.addition.as_nanos() * u128::from(counts.additions)
+            + self.multiplication.as_nanos() * u128::from(counts.multiplications)
+            + self.scalar_addition.as_nanos() * u128::from(counts.scalar_additions)
+            + self.scalar_multiplication.as_nanos() * u128::from(counts.scalar_multiplications);
+        let secs = total / 1_000_000_000;
+        let nanos = total % 1_000_000_000;
+        Duration::new(secs as u64, nanos as u32)
+    }
+}
+
+fn measure<F: Fn(Enc) -> Enc>(iterations: u32, init: Enc, op: F) -> Duration {
+    let start = Instant::now();
+    let mut acc = init;
+    for _ in 0..iterations {
+        acc = op(hint::black_box(acc));
+    }
+    hint::black_box(acc);
+    start.elapsed() / iterations
+}
+
+/// Cost estimate of a metered computation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Estimate {
+    /// Recorded operations
+    pub counts: Counts,
+    /// Number of bytes read and written by the operations
+    pub bytes_moved: u64,
+    /// Estimated running time
+    pub time: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_of_power() {
+        let meter = Meter::new();
+        let x = meter.dummy();
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        let y = x4 * x + x2 * 3 + 1;
+
+        assert_eq!(3, y.depth());
+        assert_eq!(
+            Counts {
+                additions: 1,
+                multiplications: 3,
+                scalar_additions: 1,
+                scalar_multiplications: 1,
+                depth: 3,
+            },
+            meter.counts()
+        );
+    }
+
+    #[test]
+    fn dry_run_matches_encrypted_run() {
+        let key_pair = KeyPair::new();
+        let dry = Meter::new();
+        let real = Meter::new();
+        let sum = |meter: &Meter, xs: Vec<Metered<'_>>| {
+            xs.into_iter()
+                .fold(meter.dummy(), |acc, x| acc + x * x)
+                .depth()
+        };
+
+        let dry_depth = sum(&dry, (1..10).map(|_| dry.dummy()).collect());
+        let real_depth = sum(&real, (1..10).map(|x| real.encrypt(&key_pair, x)).collect());
+
+        assert_eq!(dry_depth, real_depth);
+        assert_eq!(dry.counts(), real.counts());
+    }
+
+    #[test]
+    fn estimate_scales_with_counts() {
+        let model = CostModel {
+            addition: Duration::from_nanos(10),
+            multiplication: Duration::from_nanos(100),
+            scalar_addition: Duration::from_nanos(1),
+            scalar_multiplication: Duration::from_nanos(5),
+        };
+        let meter = Meter::new();
+        let x = meter.dummy();
+        let _ = (x + x) * x * x * 2 + 1;
+
+        let estimate = meter.estimate(&model);
+        assert_eq!(Duration::from_nanos(10 + 2 * 100 + 1 + 5), estimate.time);
+        assert_eq!(
+            (3 * 3 + 2 * 2) * mem::size_of::<Enc>() as u64,
+            estimate.bytes_moved
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Known-plaintext attacks on the scheme
+//!
+//! Available with the `cryptanalysis` feature. The experiments here play
+//! the attacker: they only look at ciphertexts and their known plaintexts,
+//! and report how much of that material was needed and whether the attack
+//! worked. The key is used solely to produce the material and to check
+//! the outcome.
+//!
+//! Decryption, the real part of `(backwards * enc * forwards)[0]`, is a
+//! linear function of the 36 coefficients of a ciphertext.
+//! `linear_key_recovery` recovers that function from known pairs by
+//! Gaussian elimination, which is enough to decrypt any ciphertext lying
+//! in the span of the observed ones, including results of homomorphic
+//! operations.
+use super::algebra::{Invertible, Mod231};
+use super::enc::{Decrypt, Enc, Encrypt, KeyPair};
+use super::noise::{NoiseStrategy, Triangular};
+use super::wire::COEFFICIENTS;
+use num_traits::Zero;
+use rand::prelude::*;
+
+/// Outcome of `linear_key_recovery`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LinearAttackReport {
+    /// Number of known (plaintext, ciphertext) pairs consumed
+    pub pairs_used: usize,
+    /// Dimension of the space spanned by the observed ciphertexts
+    pub rank: usize,
+    /// Fraction of fresh ciphertexts decrypted correctly by the attack
+    pub success_rate: f64,
+}
+
+impl LinearAttackReport {
+    /// Returns `true` if every fresh ciphertext was decrypted correctly
+    #[inline]
+    pub fn succeeded(&self) -> bool {
+        self.success_rate >= 1.0
+    }
+}
+
+/// Outcome of `trace_invariant`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraceReport {
+    /// Number of ciphertexts examined
+    pub samples: usize,
+    /// Whether the real part of the trace survives conjugation by the key
+    /// on every sample
+    pub invariant_holds: bool,
+    /// Fraction of samples where the real part of the trace equals the
+    /// plaintext
+    pub success_rate: f64,
+}
+
+/// Linear functional learnt from known pairs, kept in echelon form
+#[derive(Debug, Default)]
+struct LinearDecryptor {
+    rows: Vec<(usize, [Mod231; COEFFICIENTS], Mod231)>,
+}
+
+impl LinearDecryptor {
+    /// Reduces `coefficients` against the rows learnt so far, returning
+    /// the residue and the predicted plaintext of the reduced part
+    fn reduce(&self, mut coefficients: [Mod231; COEFFICIENTS]) -> ([Mod231; COEFFICIENTS], Mod231) {
+        let mut value = Mod231::zero();
+        for (pivot, row, row_value) in &self.rows {
+            let factor = coefficients[*pivot];
+            if factor.is_zero() {
+                continue;
+            }
+            for (c, r) in coefficients.iter_mut().zip(row.iter()) {
+                *c -= factor * *r;
+            }
+            value += factor * *row_value;
+        }
+        (coefficients, value)
+    }
+
+    /// Learns a known pair, returning `true` if it extended the span
+    fn learn(&mut self, enc: &Enc, plaintext: Mod231) -> bool {
+        let (residue, predicted) = self.reduce(enc.coefficients());
+        let pivot = match residue.iter().position(|c| !c.is_zero()) {
+            Some(pivot) => pivot,
+            None => return false,
+        };
+        let scale = residue[pivot].invert();
+        let mut row = residue;
+        for c in row.iter_mut() {
+            *c *= scale;
+        }
+        let value = (plaintext - predicted) * scale;
+        for (_, other, other_value) in self.rows.iter_mut() {
+            let factor = other[pivot];
+            if factor.is_zero() {
+                continue;
+            }
+            for (c, r) in other.iter_mut().zip(row.iter()) {
+                *c -= factor * *r;
+            }
+            *other_value -= factor * value;
+        }
+        self.rows.push((pivot, row, value));
+        true
+    }
+
+    /// Predicts the plaintext of `enc`, if it lies in the learnt span
+    fn decrypt(&self, enc: &Enc) -> Option<Mod231> {
+        let (residue, value) = self.reduce(enc.coefficients());
+        if residue.iter().all(Zero::is_zero) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+/// Recovers the decryption map from known (plaintext, ciphertext) pairs
+///
+/// Random plaintexts are encrypted under `key_pair` and handed to the
+/// attacker one by one, until `max_pairs` pairs were used or `patience`
+/// consecutive pairs in a row failed to extend the span. The recovered map
+/// is then tested on `trials` fresh ciphertexts, half of which are
+/// products and sums of fresh encryptions.
+pub fn linear_key_recovery(
+    key_pair: &KeyPair,
+    max_pairs: usize,
+    patience: usize,
+    trials: usize,
+) -> LinearAttackReport {
+    let mut rng = thread_rng();
+    let mut attack = LinearDecryptor::default();
+    let mut pairs_used = 0;
+    let mut stale = 0;
+
+    while pairs_used < max_pairs && stale < patience {
+        let plaintext = rng.gen::<u32>();
+        let enc = Enc::encrypt(key_pair, plaintext);
+        pairs_used += 1;
+        if attack.learn(&enc, Mod231::from(plaintext)) {
+            stale = 0;
+        } else {
+            stale += 1;
+        }
+    }
+
+    let successes = (0..trials)
+        .filter(|trial| {
+            let x = Enc::encrypt(key_pair, rng.gen::<u32>());
+            let enc = if trial % 2 == 0 {
+                x
+            } else {
+                let y = Enc::encrypt(key_pair, rng.gen::<u32>());
+                x * y + y
+            };
+            attack.decrypt(&enc) == Some(Mod231(enc.decrypt(key_pair)))
+        })
+        .count();
+
+    LinearAttackReport {
+        pairs_used,
+        rank: attack.rows.len(),
+        success_rate: rate(successes, trials),
+    }
+}
+
+/// Tests whether the real part of the trace of a ciphertext leaks
+/// its plaintext
+///
+/// Since `Re(ab) = Re(ba)` for quaternions, the real part of the trace is
+/// unchanged by conjugation with the key, so it equals the plaintext plus
+/// the real parts of the diagonal noise. The report states whether that
+/// invariant held, and how often the trace alone gave the plaintext away.
+#[inline]
+pub fn trace_invariant(key_pair: &KeyPair, samples: usize) -> TraceReport {
+    trace_invariant_with_noise(key_pair, &Triangular, samples)
+}
+
+/// Like `trace_invariant`, for ciphertexts hidden with the given noise
+/// strategy
+pub fn trace_invariant_with_noise<N: NoiseStrategy>(
+    key_pair: &KeyPair,
+    noise: &N,
+    samples: usize,
+) -> TraceReport {
+    let mut rng = thread_rng();
+    let mut invariant_holds = true;
+    let mut successes = 0;
+
+    for _ in 0..samples {
+        let plaintext = Mod231::from(rng.gen::<u32>());
+        let enc = Enc::encrypt_with_noise(key_pair, plaintext.0, noise);
+        let trace = real_trace(&enc.coefficients());
+        let unmasked = enc.unmask(key_pair);
+        let expected = unmasked[0].real() + unmasked[4].real() + unmasked[8].real();
+
+        invariant_holds &= trace == expected;
+        if trace == plaintext {
+            successes += 1;
+        }
+    }
+
+    TraceReport {
+        samples,
+        invariant_holds,
+        success_rate: rate(successes, samples),
+    }
+}
+
+/// Sum of the real parts of the diagonal quaternions
+fn real_trace(coefficients: &[Mod231; COEFFICIENTS]) -> Mod231 {
+    coefficients[0] + coefficients[16] + coefficients[32]
+}
+
+fn rate(successes: usize, trials: usize) -> f64 {
+    if trials == 0 {
+        0.0
+    } else {
+        successes as f64 / trials as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_attack_recovers_decryption() {
+        let key_pair = KeyPair::new();
+        let report = linear_key_recovery(&key_pair, 100, 5, 20);
+        assert!(report.rank <= COEFFICIENTS);
+        assert!(report.succeeded(), "{:?}", report);
+    }
+
+    #[test]
+    fn trace_is_invariant_but_noisy() {
+        let key_pair = KeyPair::new();
+        let report = trace_invariant(&key_pair, 20);
+        assert!(report.invariant_holds);
+        assert!(report.success_rate < 1.0);
+    }
+}
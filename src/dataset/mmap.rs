@@ -0,0 +1,253 @@
+//! Datasets in memory-mapped files
+//!
+//! Mapping a file is `unsafe`, since the memory behind the mapping changes
+//! whenever the file does, including through other processes. Callers
+//! have to make sure that mapped files are left alone while in use.
+use super::{DatasetHeader, DatasetView, DatasetWriter, COUNT_OFFSET};
+use crate::enc::{Enc, Fingerprint};
+use crate::error::Error;
+use crate::wire::{EncArray, ENC_LEN};
+use memmap2::{Mmap, MmapMut};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// Read-only dataset in a memory-mapped file
+///
+/// ## Example:
+///
+/// ```no_run
+/// use gmorph::dataset::MappedDataset;
+/// use gmorph::*;
+///
+/// let key_pair = KeyPair::default();
+/// // nothing else writes to the file while it is mapped
+/// let dataset = unsafe { MappedDataset::open("enc.gmds").unwrap() };
+/// dataset.header().check_key(&key_pair).unwrap();
+/// let sum = dataset.records().sum().unwrap();
+/// println!("{}", sum.decrypt(&key_pair));
+/// ```
+#[derive(Debug)]
+pub struct MappedDataset {
+    map: Mmap,
+    header: DatasetHeader,
+    records: Range<usize>,
+}
+
+impl MappedDataset {
+    /// Maps the dataset at `path` and parses its header
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let map = Mmap::map(&File::open(path)?)?;
+        let view = DatasetView::new(&map)?;
+        let start = view.header.len() as usize;
+        let records = start..start + view.records.len() * ENC_LEN;
+        let header = view.header;
+        Ok(Self {
+            map,
+            header,
+            records,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &DatasetHeader {
+        &self.header
+    }
+
+    /// The encoded records, read straight from the mapping
+    #[inline]
+    pub fn records(&self) -> EncArray<'_> {
+        // checked when opening
+        EncArray::new(&self.map[self.records.clone()]).unwrap()
+    }
+}
+
+/// Writes a dataset into a memory-mapped file
+///
+/// The file is grown ahead of the records, doubling its capacity whenever
+/// it runs out, and cut down to size by `finish`. Dropping the writer
+/// finishes it too, but ignores any error in doing so.
+#[derive(Debug)]
+pub struct MappedDatasetWriter {
+    file: File,
+    // taken when finishing, so that the file can shrink
+    map: Option<MmapMut>,
+    start: usize,
+    written: usize,
+    capacity: usize,
+}
+
+impl MappedDatasetWriter {
+    /// Creates the dataset at `path`, with room for `capacity` records
+    /// before the file has to grow
+    ///
+    /// Any existing file at `path` is truncated. The arguments are
+    /// checked like in `DatasetWriter::new`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by anything else until
+    /// the writer is finished or dropped.
+    pub unsafe fn create<P: AsRef<Path>>(
+        path: P,
+        fingerprint: Fingerprint,
+        columns: &[&str],
+        capacity: usize,
+    ) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let start = DatasetWriter::new(&file, fingerprint, columns)?
+            .header
+            .len() as usize;
+        let capacity = capacity.max(1);
+        file.set_len((start + capacity * ENC_LEN) as u64)?;
+        let map = MmapMut::map_mut(&file)?;
+        Ok(Self {
+            file,
+            map: Some(map),
+            start,
+            written: 0,
+            capacity,
+        })
+    }
+
+    /// Appends one record
+    pub fn push(&mut self, enc: &Enc) -> Result<(), Error> {
+        if self.written == self.capacity {
+            self.grow()?;
+        }
+        let offset = self.start + self.written * ENC_LEN;
+        self.map_mut()[offset..offset + ENC_LEN].copy_from_slice(&enc.to_bytes());
+        self.written += 1;
+        Ok(())
+    }
+
+    fn grow(&mut self) -> io::Result<()> {
+        self.map_mut().flush()?;
+        self.capacity *= 2;
+        self.file
+            .set_len((self.start + self.capacity * ENC_LEN) as u64)?;
+        // SAFETY: the caller of `create` vouched for the file to be left
+        // alone while the writer is alive
+        self.map = Some(unsafe { MmapMut::map_mut(&self.file)? });
+        Ok(())
+    }
+
+    #[inline]
+    fn map_mut(&mut self) -> &mut MmapMut {
+        // only `close` takes the mapping, and nothing writes after it
+        self.map.as_mut().expect("writer already finished")
+    }
+
+    /// Number of records written so far
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.written
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.written == 0
+    }
+
+    /// Records the number of records in the header, flushes the mapping
+    /// and cuts the file down to the records written
+    pub fn finish(mut self) -> Result<(), Error> {
+        Ok(self.close()?)
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        let mut map = match self.map.take() {
+            Some(map) => map,
+            None => return Ok(()),
+        };
+        let count = COUNT_OFFSET as usize;
+        map[count..count + 8].copy_from_slice(&(self.written as u64).to_le_bytes());
+        map.flush()?;
+        // the mapping has to go before the file shrinks under it
+        drop(map);
+        self.file
+            .set_len((self.start + self.written * ENC_LEN) as u64)
+    }
+}
+
+impl Drop for MappedDatasetWriter {
+    fn drop(&mut self) {
+        // errors can't be reported from here, `finish` returns them
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::DatasetReader;
+    use crate::enc::{Decrypt, Encrypt, KeyPair};
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gmorph-{}-{}.gmds", name, std::process::id()))
+    }
+
+    #[test]
+    fn mapped_roundtrip() {
+        let key_pair = KeyPair::new();
+        let path = temp_path("mapped-roundtrip");
+
+        let mut writer =
+            unsafe { MappedDatasetWriter::create(&path, key_pair.fingerprint(), &[], 2) }.unwrap();
+        for x in 1..=10 {
+            writer.push(&Enc::encrypt(&key_pair, x)).unwrap();
+        }
+        assert_eq!(writer.len(), 10);
+        writer.finish().unwrap();
+
+        let reader = DatasetReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.header().count(), Some(10));
+        let streamed: Vec<_> = reader.map(|x| x.unwrap().decrypt(&key_pair)).collect();
+        assert_eq!(streamed, (1..=10).collect::<Vec<_>>());
+
+        let dataset = unsafe { MappedDataset::open(&path) }.unwrap();
+        dataset.header().check_key(&key_pair).unwrap();
+        let records = dataset.records();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records.sum().unwrap().decrypt(&key_pair), 55);
+        assert_eq!(records.dot(&records).unwrap().decrypt(&key_pair), 385);
+
+        drop(dataset);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dropped_writer_is_finished() {
+        let key_pair = KeyPair::new();
+        let path = temp_path("mapped-dropped");
+
+        let mut writer =
+            unsafe { MappedDatasetWriter::create(&path, key_pair.fingerprint(), &[], 8) }.unwrap();
+        for x in 1..=3 {
+            writer.push(&Enc::encrypt(&key_pair, x)).unwrap();
+        }
+        drop(writer);
+
+        let reader = DatasetReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.header().count(), Some(3));
+        let streamed: Vec<_> = reader.map(|x| x.unwrap().decrypt(&key_pair)).collect();
+        assert_eq!(streamed, [1, 2, 3]);
+
+        let dataset = unsafe { MappedDataset::open(&path) }.unwrap();
+        assert_eq!(dataset.records().len(), 3);
+
+        drop(dataset);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
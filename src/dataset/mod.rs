@@ -0,0 +1,678 @@
+//! Files holding large numbers of ciphertexts
+//!
+//! A dataset starts with a header, followed by fixed-size records, each
+//! holding one `Enc` in the format of `Enc::to_bytes`:
+//!
+//! ```text
+//! offset  size  field
+//!      0     4  magic bytes "GMDS"
+//!      4     1  container version, currently 1
+//!      5    32  fingerprint of the key the records are encrypted with
+//!     37     8  number of records, little-endian u64, or u64::MAX if unknown
+//!     45     4  number of columns, little-endian u32, 0 if there is no schema
+//!     49        for each column, its name as a little-endian u16 length
+//!               followed by that many bytes of UTF-8
+//! ```
+//!
+//! With a schema, the records are stored row by row, so record `i` is the
+//! value in column `i % columns` of row `i / columns`.
+//!
+//! `DatasetWriter` and `DatasetReader` stream records through `io::Write`
+//! and `io::Read`, so datasets never have to fit in memory. Records can be
+//! read by index when the underlying reader implements `io::Seek`.
+//!
+//! A dataset already in memory can be read in place through `DatasetView`.
+//! With the `mmap` feature, `MappedDataset` and `MappedDatasetWriter` do
+//! the same for memory-mapped files.
+//!
+//! Errors are reported as `Error`, like everywhere else in the crate:
+//! `Error::Io` when the underlying reader or writer fails or a header is
+//! malformed, `Error::Decode` for records which are not valid ciphertexts,
+//! `Error::WrongKey` from `check_key` and `Error::Length` for rows which
+//! do not match the schema.
+//!
+//! ## Example:
+//!
+//! ```
+//! use gmorph::dataset::{DatasetReader, DatasetWriter};
+//! use gmorph::*;
+//! use std::io::Cursor;
+//!
+//! let key_pair = KeyPair::default();
+//! let mut writer =
+//!     DatasetWriter::new(Cursor::new(Vec::new()), key_pair.fingerprint(), &["x"]).unwrap();
+//! for x in 0..10 {
+//!     writer.write(&Enc::encrypt(&key_pair, x)).unwrap();
+//! }
+//! let file = writer.finish().unwrap();
+//!
+//! let mut reader = DatasetReader::new(Cursor::new(file.into_inner())).unwrap();
+//! reader.check_key(&key_pair).unwrap();
+//! assert_eq!(7, reader.get(7).unwrap().decrypt(&key_pair));
+//! ```
+#[cfg(feature = "mmap")]
+mod mmap;
+
+use super::enc::{Enc, Fingerprint, KeyPair};
+use super::error::{check_length, Error};
+use super::wire::{EncArray, ENC_LEN};
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+#[cfg(feature = "mmap")]
+pub use self::mmap::{MappedDataset, MappedDatasetWriter};
+
+const MAGIC: &[u8; 4] = b"GMDS";
+
+/// Version of the container format written by `DatasetWriter`
+pub const VERSION: u8 = 1;
+
+/// Offset of the number of records in the header
+const COUNT_OFFSET: u64 = 37;
+/// Stored in place of the number of records while it is not known
+const UNKNOWN_COUNT: u64 = u64::MAX;
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Header of a dataset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetHeader {
+    fingerprint: Fingerprint,
+    count: Option<u64>,
+    columns: Vec<String>,
+}
+
+impl DatasetHeader {
+    /// Fingerprint of the key the records are encrypted with
+    #[inline]
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    /// Number of records, if it was recorded when writing
+    #[inline]
+    pub fn count(&self) -> Option<u64> {
+        self.count
+    }
+
+    /// Names of the columns, empty if the dataset has no schema
+    #[inline]
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Checks that the records are encrypted with `key_pair`
+    pub fn check_key(&self, key_pair: &KeyPair) -> Result<(), Error> {
+        if key_pair.fingerprint() == self.fingerprint {
+            Ok(())
+        } else {
+            Err(Error::WrongKey)
+        }
+    }
+
+    /// Size of the encoded header in bytes
+    fn len(&self) -> u64 {
+        let names: usize = self.columns.iter().map(|c| 2 + c.len()).sum();
+        COUNT_OFFSET + 8 + 4 + names as u64
+    }
+
+    fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&self.fingerprint.0)?;
+        w.write_all(&self.count.unwrap_or(UNKNOWN_COUNT).to_le_bytes())?;
+        w.write_all(&(self.columns.len() as u32).to_le_bytes())?;
+        for name in self.columns.iter() {
+            w.write_all(&(name.len() as u16).to_le_bytes())?;
+            w.write_all(name.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(mut r: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a gmorph dataset"));
+        }
+        let mut version = [0u8; 1];
+        r.read_exact(&mut version)?;
+        if version[0] != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported dataset version {}",
+                version[0]
+            )));
+        }
+        let mut fingerprint = [0u8; 32];
+        r.read_exact(&mut fingerprint)?;
+        let mut count = [0u8; 8];
+        r.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count);
+        let mut columns = [0u8; 4];
+        r.read_exact(&mut columns)?;
+        let columns = u32::from_le_bytes(columns);
+
+        let columns = (0..columns)
+            .map(|_| {
+                let mut len = [0u8; 2];
+                r.read_exact(&mut len)?;
+                let mut name = vec![0u8; u16::from_le_bytes(len).into()];
+                r.read_exact(&mut name)?;
+                String::from_utf8(name).map_err(invalid_data)
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            fingerprint: Fingerprint(fingerprint),
+            count: if count == UNKNOWN_COUNT {
+                None
+            } else {
+                Some(count)
+            },
+            columns,
+        })
+    }
+}
+
+/// Streams ciphertexts into a dataset
+///
+/// The number of records is filled in by `finish`, which needs to seek
+/// back to the header. Writers which cannot seek, like pipes, are closed
+/// with `into_inner` instead, and leave the number of records unknown.
+#[derive(Debug)]
+pub struct DatasetWriter<W: Write> {
+    inner: Counted<W>,
+    header: DatasetHeader,
+    written: u64,
+}
+
+impl<W: Write> DatasetWriter<W> {
+    /// Writes the header of a dataset of ciphertexts encrypted under
+    /// the key with the given fingerprint
+    ///
+    /// `columns` names the values of each row, and may be empty if the
+    /// records are not organized in rows.
+    pub fn new(inner: W, fingerprint: Fingerprint, columns: &[&str]) -> Result<Self, Error> {
+        if columns.len() > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many columns").into());
+        }
+        if columns.iter().any(|c| c.len() > u16::MAX.into()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "column name longer than 65535 bytes",
+            )
+            .into());
+        }
+        let header = DatasetHeader {
+            fingerprint,
+            count: None,
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+        };
+        let mut inner = Counted { inner, bytes: 0 };
+        header.write_to(&mut inner)?;
+        Ok(Self {
+            inner,
+            header,
+            written: 0,
+        })
+    }
+
+    /// Appends one record
+    #[inline]
+    pub fn write(&mut self, enc: &Enc) -> Result<(), Error> {
+        self.inner.write_all(&enc.to_bytes())?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Appends one record per column
+    ///
+    /// Fails without writing anything if `row` does not match the schema.
+    pub fn write_row(&mut self, row: &[Enc]) -> Result<(), Error> {
+        check_length(self.header.columns.len(), row.len())?;
+        row.iter().try_for_each(|enc| self.write(enc))
+    }
+
+    /// Number of records written so far
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.written
+    }
+
+    /// Returns `true` if no records were written yet
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.written == 0
+    }
+
+    /// Flushes the records, leaving the number of records unknown
+    pub fn into_inner(mut self) -> Result<W, Error> {
+        self.inner.flush()?;
+        Ok(self.inner.inner)
+    }
+}
+
+impl<W: Write + Seek> DatasetWriter<W> {
+    /// Records the number of records in the header and flushes them
+    ///
+    /// The writer is left positioned at the end of the dataset.
+    pub fn finish(self) -> Result<W, Error> {
+        let Counted { mut inner, bytes } = self.inner;
+        // everything handed to `inner` went after the start of the header,
+        // including any part of a record whose write failed
+        let end = inner.stream_position()?;
+        inner.seek(SeekFrom::Start(end - bytes + COUNT_OFFSET))?;
+        inner.write_all(&self.written.to_le_bytes())?;
+        inner.seek(SeekFrom::Start(end))?;
+        inner.flush()?;
+        Ok(inner)
+    }
+}
+
+/// Writer counting the bytes accepted by `inner`, including those of a
+/// write which failed halfway
+///
+/// `DatasetWriter::new` cannot ask a writer which may not seek for its
+/// position, so `finish` finds the header from this count instead.
+#[derive(Debug)]
+struct Counted<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Streams ciphertexts out of a dataset
+///
+/// Iterating yields the records in order, and stops after the first
+/// error. With a seekable reader, records can also be fetched by index with
+/// `get`, which moves the position of the iterator to the record after it.
+#[derive(Debug)]
+pub struct DatasetReader<R: Read> {
+    inner: R,
+    header: DatasetHeader,
+    /// Length of the header
+    start: u64,
+    /// Position of the header in `inner`, found on the first seek
+    base: Option<u64>,
+    /// Bytes of records consumed from `inner`, complete or not
+    consumed: u64,
+    /// Index of the next record
+    position: u64,
+    /// Set once iteration has hit an error
+    failed: bool,
+}
+
+impl<R: Read> DatasetReader<R> {
+    /// Reads the header of a dataset
+    pub fn new(mut inner: R) -> Result<Self, Error> {
+        let header = DatasetHeader::read_from(&mut inner)?;
+        Ok(Self {
+            inner,
+            start: header.len(),
+            header,
+            base: None,
+            consumed: 0,
+            position: 0,
+            failed: false,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &DatasetHeader {
+        &self.header
+    }
+
+    /// Checks that the records are encrypted with `key_pair`
+    #[inline]
+    pub fn check_key(&self, key_pair: &KeyPair) -> Result<(), Error> {
+        self.header.check_key(key_pair)
+    }
+
+    /// Reads the next record, or returns `None` at the end of the dataset
+    pub fn read_record(&mut self) -> Result<Option<Enc>, Error> {
+        if Some(self.position) == self.header.count {
+            return Ok(None);
+        }
+        let mut bytes = [0u8; ENC_LEN];
+        let mut filled = 0;
+        while filled < ENC_LEN {
+            match self.inner.read(&mut bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    filled += n;
+                    self.consumed += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match filled {
+            0 if self.header.count.is_none() => Ok(None),
+            ENC_LEN => {
+                self.position += 1;
+                Ok(Some(Enc::from_bytes(&bytes)?))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("dataset ends inside record {}", self.position),
+            )
+            .into()),
+        }
+    }
+
+    /// Unwraps the underlying reader
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek> DatasetReader<R> {
+    fn base(&mut self) -> io::Result<u64> {
+        if let Some(base) = self.base {
+            return Ok(base);
+        }
+        let base = self.inner.stream_position()? - self.consumed - self.start;
+        self.base = Some(base);
+        Ok(base)
+    }
+
+    /// Number of records, taken from the header or, if it was not
+    /// recorded, from the size of the dataset
+    pub fn len(&mut self) -> Result<u64, Error> {
+        if let Some(count) = self.header.count {
+            return Ok(count);
+        }
+        let base = self.base()?;
+        let current = self.inner.stream_position()?;
+        let end = self.inner.seek(SeekFrom::End(0))?;
+        self.inner.seek(SeekFrom::Start(current))?;
+        Ok((end - base - self.start) / ENC_LEN as u64)
+    }
+
+    /// Returns `true` if the dataset holds no records
+    pub fn is_empty(&mut self) -> Result<bool, Error> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Reads the record at `index`
+    pub fn get(&mut self, index: u64) -> Result<Enc, Error> {
+        if index >= self.len()? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("record {} is out of bounds", index),
+            )
+            .into());
+        }
+        let offset = index * ENC_LEN as u64;
+        let base = self.base()?;
+        self.inner
+            .seek(SeekFrom::Start(base + self.start + offset))?;
+        self.consumed = offset;
+        self.position = index;
+        self.failed = false;
+        self.read_record()?.ok_or_else(|| {
+            Error::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("dataset ends before record {}", index),
+            ))
+        })
+    }
+}
+
+impl<R: Read> Iterator for DatasetReader<R> {
+    type Item = Result<Enc, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.read_record().transpose();
+        self.failed = matches!(next, Some(Err(_)));
+        next
+    }
+}
+
+/// Dataset read in place from a byte slice
+///
+/// Only the header is parsed up front. The records stay encoded and are
+/// accessed through `records`, see `EncArray`.
+#[derive(Debug, Clone)]
+pub struct DatasetView<'a> {
+    header: DatasetHeader,
+    records: EncArray<'a>,
+}
+
+impl<'a> DatasetView<'a> {
+    /// Parses the header of the dataset held in `bytes`
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        let header = DatasetHeader::read_from(bytes)?;
+        let records = &bytes[header.len() as usize..];
+        let records = match header.count {
+            None => records,
+            Some(count) => usize::try_from(count)
+                .ok()
+                .and_then(|count| count.checked_mul(ENC_LEN))
+                .and_then(|len| records.get(..len))
+                .ok_or_else(|| {
+                    Error::Io(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("dataset ends before record {}", count),
+                    ))
+                })?,
+        };
+        let records = EncArray::new(records)?;
+        Ok(Self { header, records })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &DatasetHeader {
+        &self.header
+    }
+
+    /// The encoded records
+    #[inline]
+    pub fn records(&self) -> EncArray<'a> {
+        self.records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enc::{Decrypt, Encrypt};
+    use std::io::Cursor;
+
+    fn dataset(key_pair: &KeyPair, values: &[u32], columns: &[&str], finish: bool) -> Vec<u8> {
+        let mut writer =
+            DatasetWriter::new(Cursor::new(Vec::new()), key_pair.fingerprint(), columns).unwrap();
+        for x in values.iter() {
+            writer.write(&Enc::encrypt(key_pair, *x)).unwrap();
+        }
+        assert_eq!(writer.len(), values.len() as u64);
+        let cursor = if finish {
+            writer.finish().unwrap()
+        } else {
+            writer.into_inner().unwrap()
+        };
+        cursor.into_inner()
+    }
+
+    #[quickcheck]
+    fn prop_stream_roundtrip(values: Vec<u32>, finish: bool) -> bool {
+        let key_pair = KeyPair::new();
+        let bytes = dataset(&key_pair, &values, &[], finish);
+        let reader = DatasetReader::new(&bytes[..]).unwrap();
+        let expected = if finish {
+            Some(values.len() as u64)
+        } else {
+            None
+        };
+        let header_ok = reader.header().count() == expected
+            && reader.header().fingerprint() == key_pair.fingerprint();
+        let decrypted: Vec<_> = reader.map(|x| x.unwrap().decrypt(&key_pair)).collect();
+        let values: Vec<_> = values.iter().map(|x| x % crate::algebra::MODULUS).collect();
+        header_ok && decrypted == values
+    }
+
+    #[test]
+    fn random_access() {
+        let key_pair = KeyPair::new();
+        let values: Vec<u32> = (100..120).collect();
+        for finish in [false, true].iter() {
+            let bytes = dataset(&key_pair, &values, &["a", "b"], *finish);
+            let mut reader = DatasetReader::new(Cursor::new(bytes)).unwrap();
+            assert_eq!(reader.header().columns(), ["a", "b"]);
+            assert_eq!(reader.len().unwrap(), 20);
+            for i in [7, 3, 19, 0, 8].iter() {
+                assert_eq!(reader.get(*i).unwrap().decrypt(&key_pair), 100 + *i as u32);
+            }
+            // iteration continues after the last record fetched
+            assert_eq!(reader.next().unwrap().unwrap().decrypt(&key_pair), 109);
+            assert!(reader.get(20).is_err());
+        }
+    }
+
+    #[test]
+    fn get_after_truncated_record() {
+        let key_pair = KeyPair::new();
+        for finish in [false, true].iter() {
+            let mut bytes = dataset(&key_pair, &[1, 2, 3], &[], *finish);
+            bytes.truncate(bytes.len() - ENC_LEN / 2);
+            // the dataset does not start at the beginning of the stream
+            let mut stream = vec![0xff; 5];
+            stream.extend_from_slice(&bytes);
+            let mut stream = Cursor::new(stream);
+            stream.set_position(5);
+
+            let mut reader = DatasetReader::new(stream).unwrap();
+            assert_eq!(reader.next().unwrap().unwrap().decrypt(&key_pair), 1);
+            assert_eq!(reader.next().unwrap().unwrap().decrypt(&key_pair), 2);
+            let err = reader.next().unwrap().unwrap_err();
+            assert!(matches!(err, Error::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+            assert_eq!(reader.get(1).unwrap().decrypt(&key_pair), 2);
+            assert_eq!(reader.get(0).unwrap().decrypt(&key_pair), 1);
+            assert!(reader.get(2).is_err());
+            assert_eq!(reader.get(1).unwrap().decrypt(&key_pair), 2);
+        }
+    }
+
+    /// Accepts `budget` more bytes, then fails
+    struct Flaky {
+        inner: Cursor<Vec<u8>>,
+        budget: usize,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.budget == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "out of budget"));
+            }
+            let n = self.inner.write(&buf[..buf.len().min(self.budget)])?;
+            self.budget -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for Flaky {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn finish_after_failed_write() {
+        let key_pair = KeyPair::new();
+        let mut inner = Cursor::new(vec![0xff; 5]);
+        inner.set_position(5);
+        let flaky = Flaky {
+            inner,
+            budget: usize::MAX,
+        };
+        let mut writer = DatasetWriter::new(flaky, key_pair.fingerprint(), &["x"]).unwrap();
+        writer.write(&Enc::encrypt(&key_pair, 1)).unwrap();
+        writer.write(&Enc::encrypt(&key_pair, 2)).unwrap();
+        writer.inner.inner.budget = ENC_LEN / 2;
+        assert!(writer.write(&Enc::encrypt(&key_pair, 3)).is_err());
+        writer.inner.inner.budget = usize::MAX;
+
+        let bytes = writer.finish().unwrap().inner.into_inner();
+        let reader = DatasetReader::new(&bytes[5..]).unwrap();
+        assert_eq!(reader.header().count(), Some(2));
+        let decrypted: Vec<_> = reader.map(|x| x.unwrap().decrypt(&key_pair)).collect();
+        assert_eq!(decrypted, [1, 2]);
+    }
+
+    #[test]
+    fn rows_must_match_schema() {
+        let key_pair = KeyPair::new();
+        let enc = Enc::encrypt(&key_pair, 1);
+        let mut writer =
+            DatasetWriter::new(Vec::new(), key_pair.fingerprint(), &["a", "b"]).unwrap();
+        assert!(matches!(
+            writer.write_row(&[enc]),
+            Err(Error::Length {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(writer.write_row(&[enc, enc]).is_ok());
+        assert_eq!(writer.len(), 2);
+    }
+
+    #[test]
+    fn malformed_datasets_are_rejected() {
+        let key_pair = KeyPair::new();
+        let bytes = dataset(&key_pair, &[1, 2, 3], &[], true);
+
+        assert!(DatasetReader::new(&bytes[1..]).is_err());
+        assert!(DatasetReader::new(&bytes[..20]).is_err());
+        let truncated: Vec<_> = DatasetReader::new(&bytes[..bytes.len() - 1])
+            .unwrap()
+            .collect();
+        assert_eq!(truncated.len(), 3);
+        assert!(truncated[2].is_err());
+
+        let reader = DatasetReader::new(&bytes[..]).unwrap();
+        assert!(reader.check_key(&key_pair).is_ok());
+        assert!(matches!(
+            reader.check_key(&KeyPair::new()),
+            Err(Error::WrongKey)
+        ));
+    }
+
+    #[test]
+    fn view_reads_in_place() {
+        let key_pair = KeyPair::new();
+        let values: Vec<u32> = (1..=10).collect();
+        for finish in [false, true].iter() {
+            let bytes = dataset(&key_pair, &values, &["x"], *finish);
+            let view = DatasetView::new(&bytes).unwrap();
+            assert_eq!(view.header().columns(), ["x"]);
+            assert_eq!(view.records().len(), 10);
+            assert_eq!(view.records().sum().unwrap().decrypt(&key_pair), 55);
+            assert!(DatasetView::new(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+}
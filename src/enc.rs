@@ -1,43 +1,335 @@
 //! Types and traits which lift `u32` type to FHE compatible
 //! `Enc` struct
-use super::algebra::{invert_3x3, Mod231, Q231};
-use nalgebra::Matrix3;
-use num_traits::Zero;
-use rand::prelude::*;
+use super::algebra::{dot, invert_3x3, mul_3x3, Matrix3, Mod231, QuaternionM, C231, Q231};
+#[cfg(feature = "alloc")]
+use super::algebra::{join, split, Invertible};
+use super::armor::{self, ParseError};
+use super::error::{check_length, Error};
+use super::noise::{NoiseStrategy, Triangular};
+use super::rational::{self, Fraction};
+use super::wire::{self, DecodeError, COEFFICIENTS, ENC_LEN, KEY_LEN};
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::{Add, AddAssign, Mul, MulAssign};
+use core::str::FromStr;
+use num_traits::{One, Zero};
+#[cfg(any(test, feature = "testkit"))]
+use quickcheck::{Arbitrary, Gen};
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::Rng;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use std::ops::{Add, AddAssign, Mul, MulAssign};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
 
 /// Wrapper type for lifting `u32` type to FHE compatible
 /// form
 ///
 /// All FHE operations (currently, addition and multiplication)
 /// are defined in terms of this type.
-#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Enc {
     inner: Matrix3<Q231>,
 }
 
 impl Enc {
+    #[cfg(feature = "std")]
     #[inline]
     fn enc(key_pair: &KeyPair, value: u32) -> Self {
-        let enc: Matrix3<_> = Q231::from(Mod231::from(value)).into();
-        let inner = key_pair.forwards * enc * key_pair.backwards;
+        Self::encrypt_with_noise(key_pair, value, &Triangular)
+    }
 
-        Self { inner }
+    /// Encrypts `value` using `key_pair`, hiding it with the given
+    /// noise strategy instead of the default one
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn encrypt_with_noise<N: NoiseStrategy>(key_pair: &KeyPair, value: u32, noise: &N) -> Self {
+        Self::encrypt_with_noise_and_rng(key_pair, value, noise, &mut thread_rng())
+    }
+
+    /// Encrypts `value` using `key_pair`, drawing the noise from `rng`
+    ///
+    /// Available without `std`, where there is no default RNG.
+    #[inline]
+    pub fn encrypt_with_rng<R: Rng + ?Sized>(key_pair: &KeyPair, value: u32, rng: &mut R) -> Self {
+        Self::encrypt_with_noise_and_rng(key_pair, value, &Triangular, rng)
+    }
+
+    /// Encrypts `value` using `key_pair`, hiding it with the given
+    /// noise strategy and drawing the noise from `rng`
+    #[inline]
+    pub fn encrypt_with_noise_and_rng<N, R>(
+        key_pair: &KeyPair,
+        value: u32,
+        noise: &N,
+        rng: &mut R,
+    ) -> Self
+    where
+        N: NoiseStrategy,
+        R: Rng + ?Sized,
+    {
+        Self::mask(key_pair, &noise.embed(Q231::from(Mod231::from(value)), rng))
+    }
+
+    /// Encrypts a whole quaternion using `key_pair`
+    ///
+    /// Sums and products of such ciphertexts decrypt to the sums and
+    /// products of the quaternions, in the same order, so multiplication
+    /// is not commutative. Decrypt with `decrypt_quaternion`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn encrypt_quaternion(key_pair: &KeyPair, value: Q231) -> Self {
+        Self::encrypt_quaternion_with_rng(key_pair, value, &mut thread_rng())
+    }
+
+    /// Encrypts a whole quaternion using `key_pair`, drawing the noise
+    /// from `rng`
+    #[inline]
+    pub fn encrypt_quaternion_with_rng<R: Rng + ?Sized>(
+        key_pair: &KeyPair,
+        value: Q231,
+        rng: &mut R,
+    ) -> Self {
+        Self::mask(key_pair, &Triangular.embed(value, rng))
+    }
+
+    /// Encrypts a 2x2 matrix over the field, given row by row
+    ///
+    /// Quaternions over the field are isomorphic to 2x2 matrices, see
+    /// `Q231`, so the ciphertexts follow matrix addition and
+    /// multiplication. Decrypt with `decrypt_matrix`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn encrypt_matrix(key_pair: &KeyPair, value: [[Mod231; 2]; 2]) -> Self {
+        Self::encrypt_quaternion(key_pair, Q231::from(value))
+    }
+
+    /// Encrypts a complex number, as the quaternion `re + im * i`
+    ///
+    /// Complex numbers form a commutative subalgebra of the quaternions,
+    /// so the ciphertexts follow complex addition and multiplication.
+    /// Decrypt with `decrypt_complex`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn encrypt_complex(key_pair: &KeyPair, value: C231) -> Self {
+        Self::encrypt_quaternion(key_pair, Q231::from(value))
+    }
+
+    /// Hides an embedding matrix under the key
+    #[inline]
+    fn mask(key_pair: &KeyPair, embedding: &Matrix3<Q231>) -> Self {
+        Self {
+            inner: mul_3x3(&mul_3x3(&key_pair.forwards, embedding), &key_pair.backwards),
+        }
     }
 
     #[inline]
     fn dec(&self, key_pair: &KeyPair) -> u32 {
-        let dec = key_pair.backwards * self.inner * key_pair.forwards;
-        dec[0].w.0
+        self.decrypt_residue(key_pair).value()
+    }
+
+    /// Decrypts the ciphertext, checking that it belongs to `key_pair`
+    ///
+    /// Unmasking with the right key leaves a plaintext at (0, 0) and zeros
+    /// below it, see the `noise` module. Under any other key, or after
+    /// tampering, these entries are random, so the check fails with
+    /// overwhelming probability and `Error::WrongKey` is returned rather
+    /// than a meaningless value. Ciphertexts of quaternions other than
+    /// field elements fail with `Error::NotScalar`.
+    #[inline]
+    pub fn try_decrypt(&self, key_pair: &KeyPair) -> Result<u32, Error> {
+        self.try_decrypt_residue(key_pair).map(Mod231::value)
+    }
+
+    /// Decrypts the ciphertext to an element of the field
+    ///
+    /// Carries the same value as `decrypt`, but as a `Mod231`, ready for
+    /// further field arithmetic or for `rational::reconstruct`.
+    #[inline]
+    pub fn decrypt_residue(&self, key_pair: &KeyPair) -> Mod231 {
+        self.unmask(key_pair)[0].real()
+    }
+
+    /// Like `decrypt_residue`, checking that the ciphertext belongs to
+    /// `key_pair` as `try_decrypt` does
+    ///
+    /// Fails with `Error::NotScalar` if the plaintext is a quaternion
+    /// other than a field element, see `encrypt_quaternion`.
+    pub fn try_decrypt_residue(&self, key_pair: &KeyPair) -> Result<Mod231, Error> {
+        let plain = self.try_decrypt_quaternion(key_pair)?;
+        if plain == Q231::from(plain.real()) {
+            Ok(plain.real())
+        } else {
+            Err(Error::NotScalar)
+        }
+    }
+
+    /// Decrypts a ciphertext created with `encrypt_quaternion`
+    ///
+    /// Ciphertexts of field elements decrypt to real quaternions.
+    #[inline]
+    pub fn decrypt_quaternion(&self, key_pair: &KeyPair) -> Q231 {
+        self.unmask(key_pair)[0]
+    }
+
+    /// Like `decrypt_quaternion`, checking that the ciphertext belongs
+    /// to `key_pair` as `try_decrypt` does
+    pub fn try_decrypt_quaternion(&self, key_pair: &KeyPair) -> Result<Q231, Error> {
+        let plain = self.unmask(key_pair);
+        if plain[1].is_zero() && plain[2].is_zero() {
+            Ok(plain[0])
+        } else {
+            Err(Error::WrongKey)
+        }
+    }
+
+    /// Decrypts a ciphertext created with `encrypt_complex`
+    ///
+    /// Only the real and `i` parts of the plaintext are kept, see
+    /// `try_decrypt_complex`.
+    #[inline]
+    pub fn decrypt_complex(&self, key_pair: &KeyPair) -> C231 {
+        let q = QuaternionM::from(self.decrypt_quaternion(key_pair));
+        C231::new(q.w, q.i)
+    }
+
+    /// Like `decrypt_complex`, checking that the ciphertext belongs to
+    /// `key_pair` as `try_decrypt` does
+    ///
+    /// Fails with `Error::NotComplex` if the plaintext has `j` or `k`
+    /// parts, as ciphertexts from `encrypt_quaternion` may.
+    #[inline]
+    pub fn try_decrypt_complex(&self, key_pair: &KeyPair) -> Result<C231, Error> {
+        C231::try_from(self.try_decrypt_quaternion(key_pair)?)
+    }
+
+    /// Decrypts a ciphertext created with `encrypt_matrix`, row by row
+    #[inline]
+    pub fn decrypt_matrix(&self, key_pair: &KeyPair) -> [[Mod231; 2]; 2] {
+        self.decrypt_quaternion(key_pair).into()
+    }
+
+    /// Decrypts the ciphertext to the fraction with numerator and
+    /// denominator of magnitude at most `rational::BALANCED_BOUND`
+    /// which it reduces to, if there is one
+    ///
+    /// Division by a plaintext constant is multiplication by its inverse
+    /// in the field, see the `rational` module.
+    #[inline]
+    pub fn decrypt_fraction(&self, key_pair: &KeyPair) -> Option<Fraction> {
+        rational::reconstruct(self.decrypt_residue(key_pair))
+    }
+
+    /// Strips the key off the ciphertext, exposing the embedding matrix
+    /// together with its noise
+    #[inline]
+    pub(crate) fn unmask(&self, key_pair: &KeyPair) -> Matrix3<Q231> {
+        mul_3x3(
+            &mul_3x3(&key_pair.backwards, &self.inner),
+            &key_pair.forwards,
+        )
+    }
+
+    /// Computes the sum of `lhs[i] * rhs[i]`
+    ///
+    /// Faster than folding over the products, since all the products are
+    /// accumulated before reducing modulo 2^31 - 1 just once.
+    ///
+    /// Panics if the slices differ in length.
+    pub fn dot(lhs: &[Enc], rhs: &[Enc]) -> Enc {
+        assert_eq!(
+            lhs.len(),
+            rhs.len(),
+            "dot product of slices of different length"
+        );
+        Self {
+            inner: dot(lhs
+                .iter()
+                .map(|x| &x.inner)
+                .zip(rhs.iter().map(|x| &x.inner))),
+        }
+    }
+
+    /// Like `dot`, but failing instead of panicking if the slices differ
+    /// in length
+    pub fn try_dot(lhs: &[Enc], rhs: &[Enc]) -> Result<Enc, Error> {
+        check_length(lhs.len(), rhs.len())?;
+        Ok(Self::dot(lhs, rhs))
+    }
+
+    /// Like `dot`, but taking the pairs of factors by value
+    pub(crate) fn dot_iter<I: IntoIterator<Item = (Enc, Enc)>>(pairs: I) -> Enc {
+        Self {
+            inner: dot(pairs.into_iter().map(|(a, b)| (a.inner, b.inner))),
+        }
+    }
+
+    /// The ciphertext as a 6x6 matrix over the field
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn split(&self) -> [[Mod231; 6]; 6] {
+        split(&self.inner)
+    }
+
+    /// Inverse of `split`
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn join(m: &[[Mod231; 6]; 6]) -> Self {
+        Self { inner: join(m) }
+    }
+
+    /// Coefficients of the ciphertext, quaternion by quaternion in
+    /// column-major order, each as `w, i, j, k`
+    pub(crate) fn coefficients(&self) -> [Mod231; COEFFICIENTS] {
+        let mut out = [Mod231(0); COEFFICIENTS];
+        for (index, chunk) in out.chunks_mut(4).enumerate() {
+            let q = QuaternionM::from(self.inner[index]);
+            chunk.copy_from_slice(&[q.w, q.i, q.j, q.k]);
+        }
+        out
+    }
+
+    /// Inverse of `coefficients`
+    pub(crate) fn from_coefficients(coefficients: &[Mod231; COEFFICIENTS]) -> Self {
+        let mut inner = Matrix3::zeros();
+        for (index, q) in coefficients.chunks(4).enumerate() {
+            inner[index] = Q231::new(q[0], q[1], q[2], q[3]);
+        }
+        Self { inner }
+    }
+
+    /// Encodes the ciphertext in the compact binary format described in
+    /// the `wire` module
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; ENC_LEN] {
+        wire::encode(&self.coefficients())
+    }
+
+    /// Decodes a ciphertext written by `to_bytes`
+    #[inline]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        wire::decode(bytes).map(|c| Self::from_coefficients(&c))
     }
 }
 
 impl fmt::Display for Enc {
+    /// Writes the ciphertext in armored form, see the `armor` module
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.inner.fmt(f)
+        armor::write(f, &armor::ENC, &self.to_bytes())
+    }
+}
+
+impl FromStr for Enc {
+    type Err = ParseError;
+
+    /// Parses the armored form written by `Display`
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        armor::read(s, &armor::ENC, Enc::from_bytes)
     }
 }
 
@@ -65,7 +357,7 @@ impl Mul for Enc {
     #[inline]
     fn mul(self, rhs: Self) -> Self::Output {
         Self {
-            inner: self.inner * rhs.inner,
+            inner: mul_3x3(&self.inner, &rhs.inner),
         }
     }
 }
@@ -77,34 +369,273 @@ impl MulAssign for Enc {
     }
 }
 
+impl Zero for Enc {
+    /// Trivial encryption of zero, which decrypts to zero under any key
+    ///
+    /// Useful as the starting point of a fold, or as a dummy
+    /// ciphertext for dry runs.
+    #[inline]
+    fn zero() -> Self {
+        Self {
+            inner: Matrix3::zero(),
+        }
+    }
+
+    /// Whether this is the trivial ciphertext returned by `zero`
+    ///
+    /// This does not tell whether the plaintext is zero: an encryption of
+    /// zero carries noise and a key, and is never trivial.
+    #[inline]
+    fn is_zero(&self) -> bool {
+        self.inner.is_zero()
+    }
+}
+
+impl One for Enc {
+    /// Trivial encryption of one, which decrypts to one under any key
+    ///
+    /// The identity matrix commutes with the key, which makes it the
+    /// starting point of a product.
+    #[inline]
+    fn one() -> Self {
+        Self {
+            inner: Matrix3::identity(),
+        }
+    }
+}
+
+impl Add<u32> for Enc {
+    type Output = Self;
+
+    /// Adds a plaintext constant to the encrypted value
+    #[inline]
+    fn add(self, rhs: u32) -> Self::Output {
+        self + Mod231::from(rhs)
+    }
+}
+
+impl Add<Mod231> for Enc {
+    type Output = Self;
+
+    /// Adds a plaintext field element to the encrypted value
+    ///
+    /// Since `backwards * forwards` is the identity, adding a scalar
+    /// multiple of the identity matrix commutes with the key.
+    #[inline]
+    fn add(self, rhs: Mod231) -> Self::Output {
+        Self {
+            inner: self.inner + Matrix3::from_diagonal_element(Q231::from(rhs)),
+        }
+    }
+}
+
+impl AddAssign<u32> for Enc {
+    #[inline]
+    fn add_assign(&mut self, rhs: u32) {
+        *self = *self + rhs
+    }
+}
+
+impl Mul<u32> for Enc {
+    type Output = Self;
+
+    /// Multiplies the encrypted value by a plaintext constant
+    #[inline]
+    fn mul(self, rhs: u32) -> Self::Output {
+        self * Mod231::from(rhs)
+    }
+}
+
+impl Mul<Mod231> for Enc {
+    type Output = Self;
+
+    /// Multiplies the encrypted value by a plaintext field element, e.g.
+    /// by the inverse of a constant to divide by it
+    #[inline]
+    fn mul(self, rhs: Mod231) -> Self::Output {
+        Self {
+            inner: self.inner.map(|x| x.scale(rhs)),
+        }
+    }
+}
+
+impl MulAssign<u32> for Enc {
+    #[inline]
+    fn mul_assign(&mut self, rhs: u32) {
+        *self = *self * rhs
+    }
+}
+
+#[cfg(any(test, feature = "testkit"))]
+impl Arbitrary for Enc {
+    /// Generates a random ciphertext, not tied to any key
+    fn arbitrary<G: Gen>(g: &mut G) -> Enc {
+        Self {
+            inner: Matrix3::zero().map(|_: Q231| Q231::arbitrary(g)),
+        }
+    }
+}
+
 /// Type representing a key pair which can be used for encrypting
 /// and decrypting data
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawKeyPair"))]
 pub struct KeyPair {
     forwards: Matrix3<Q231>,
     backwards: Matrix3<Q231>,
 }
 
+/// Unchecked serialized form of `KeyPair`
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "KeyPair")]
+struct RawKeyPair {
+    forwards: Matrix3<Q231>,
+    backwards: Matrix3<Q231>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawKeyPair> for KeyPair {
+    type Error = DecodeError;
+
+    fn try_from(raw: RawKeyPair) -> Result<Self, Self::Error> {
+        check_inverse(&raw.forwards, &raw.backwards)?;
+        Ok(Self {
+            forwards: raw.forwards,
+            backwards: raw.backwards,
+        })
+    }
+}
+
 impl KeyPair {
     /// Generates new random key pair
+    #[cfg(feature = "std")]
     #[inline]
     pub fn new() -> Self {
-        let mut rng = thread_rng();
-        let mut forwards = Matrix3::<Q231>::zero().map(|_| rng.gen::<Q231>());
-        let mut maybe_backwards = invert_3x3(&forwards);
+        Self::generate(&mut thread_rng())
+    }
 
-        while maybe_backwards.is_none() {
-            forwards = Matrix3::<Q231>::zero().map(|_| rng.gen::<Q231>());
-            maybe_backwards = invert_3x3(&forwards);
+    /// Generates new random key pair, drawing it from `rng`
+    ///
+    /// Available without `std`, where there is no default RNG.
+    pub fn generate<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        loop {
+            let forwards = Matrix3::<Q231>::zero().map(|_| rng.gen::<Q231>());
+            if let Some(backwards) = invert_3x3(&forwards) {
+                return Self {
+                    forwards,
+                    backwards,
+                };
+            }
         }
+    }
 
-        Self {
+    /// Fingerprint identifying the key pair
+    ///
+    /// Ciphertexts can be tagged with it to check that they are decrypted
+    /// with the key they were encrypted with, without storing the key.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let forwards = Enc {
+            inner: self.forwards,
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(b"gmorph key fingerprint v1");
+        hasher.update(&forwards.to_bytes()[..]);
+        Fingerprint(hasher.finalize().into())
+    }
+
+    /// Encodes the key pair as its two matrices, each in the format of
+    /// `Enc::to_bytes`
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; KEY_LEN] {
+        pair_to_bytes(&self.forwards, &self.backwards)
+    }
+
+    /// Decodes a key pair written by `to_bytes`
+    #[inline]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let (forwards, backwards) = pair_from_bytes(bytes)?;
+        Ok(Self {
             forwards,
-            backwards: maybe_backwards.unwrap(),
+            backwards,
+        })
+    }
+
+    /// Loads a key pair from a file holding either its armored form or
+    /// its binary encoding
+    #[cfg(feature = "std")]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        match core::str::from_utf8(&bytes) {
+            Ok(text) if text.trim_start().starts_with("gmorph-") => Ok(text.parse()?),
+            _ => Ok(Self::from_bytes(&bytes)?),
         }
     }
+
+    /// Saves the key pair to a file in armored form, to be read back
+    /// by `load`
+    #[cfg(feature = "std")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, format!("{}\n", self))?;
+        Ok(())
+    }
 }
 
+impl fmt::Display for KeyPair {
+    /// Writes the key pair in armored form, see the `armor` module
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        armor::write(f, &armor::KEY_PAIR, &self.to_bytes())
+    }
+}
+
+impl FromStr for KeyPair {
+    type Err = ParseError;
+
+    /// Parses the armored form written by `Display`
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        armor::read(s, &armor::KEY_PAIR, KeyPair::from_bytes)
+    }
+}
+
+/// SHA-256 digest of a key pair, see `KeyPair::fingerprint`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl fmt::Display for Fingerprint {
+    /// Formats the fingerprint as lowercase hex
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in self.0.iter() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl KeyPair {
+    /// Coefficients `l` such that decrypting `enc` gives the sum of
+    /// `l[r][c] * enc.split()[r][c]`
+    ///
+    /// Decryption takes half the trace of the top-left 2x2 block of
+    /// `backwards * enc * forwards`, which is linear in `enc`.
+    pub(crate) fn decryption_functional(&self) -> [[Mod231; 6]; 6] {
+        let b = split(&self.backwards);
+        let f = split(&self.forwards);
+        let half = Mod231(2).invert();
+        let mut out = [[Mod231(0); 6]; 6];
+        for (j, row) in out.iter_mut().enumerate() {
+            for (k, l) in row.iter_mut().enumerate() {
+                *l = (b[0][j] * f[k][0] + b[1][j] * f[k][1]) * half;
+            }
+        }
+        out
+    }
+}
+
+#[cfg(feature = "std")]
 impl Default for KeyPair {
     /// Creates randomized key pair
     #[inline]
@@ -113,6 +644,37 @@ impl Default for KeyPair {
     }
 }
 
+/// Encodes both halves of a key, see `KeyPair::to_bytes`
+fn pair_to_bytes(a: &Matrix3<Q231>, b: &Matrix3<Q231>) -> [u8; KEY_LEN] {
+    let mut out = [0u8; KEY_LEN];
+    out[..ENC_LEN].copy_from_slice(&Enc { inner: *a }.to_bytes());
+    out[ENC_LEN..].copy_from_slice(&Enc { inner: *b }.to_bytes());
+    out
+}
+
+/// Decodes both halves of a key, checking that they are inverse to
+/// each other
+fn pair_from_bytes(bytes: &[u8]) -> Result<(Matrix3<Q231>, Matrix3<Q231>), DecodeError> {
+    if bytes.len() != KEY_LEN {
+        return Err(DecodeError::Length {
+            expected: KEY_LEN,
+            found: bytes.len(),
+        });
+    }
+    let a = Enc::from_bytes(&bytes[..ENC_LEN])?.inner;
+    let b = Enc::from_bytes(&bytes[ENC_LEN..])?.inner;
+    check_inverse(&a, &b)?;
+    Ok((a, b))
+}
+
+fn check_inverse(a: &Matrix3<Q231>, b: &Matrix3<Q231>) -> Result<(), DecodeError> {
+    if mul_3x3(a, b) == Matrix3::identity() {
+        Ok(())
+    } else {
+        Err(DecodeError::NotInverse)
+    }
+}
+
 /// Helper trait for encrypting data
 pub trait Encrypt {
     type Output;
@@ -129,6 +691,7 @@ pub trait Decrypt {
     fn decrypt(&self, key_pair: &KeyPair) -> Self::Output;
 }
 
+#[cfg(feature = "std")]
 impl Encrypt for Enc {
     type Output = Enc;
 
@@ -138,6 +701,7 @@ impl Encrypt for Enc {
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> Encrypt for &'a Enc {
     type Output = Enc;
 
@@ -168,6 +732,223 @@ impl<'a> Decrypt for &'a Enc {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quickcheck::TestResult;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn wire_format() {
+        let inner = Matrix3::from_fn(|r, c| {
+            let w = Mod231((3 * r + c) as u32);
+            Q231::new(w, Mod231(1), Mod231(0), Mod231(0))
+        });
+        let json = serde_json::to_string(&Enc { inner }).unwrap();
+        let entries: Vec<_> = [0, 3, 6, 1, 4, 7, 2, 5, 8]
+            .iter()
+            .map(|w| format!("{{\"inner\":[1,0,0,{}]}}", w))
+            .collect();
+        assert_eq!(json, format!("{{\"inner\":[{}]}}", entries.join(",")));
+        assert_eq!(serde_json::from_str::<Enc>(&json).unwrap().inner, inner);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn fingerprint_identifies_key() {
+        let key_pair = KeyPair::new();
+        let json = serde_json::to_string(&key_pair).unwrap();
+        let copy: KeyPair = serde_json::from_str(&json).unwrap();
+        assert_eq!(key_pair.fingerprint(), copy.fingerprint());
+        assert_ne!(key_pair.fingerprint(), KeyPair::new().fingerprint());
+        assert_eq!(key_pair.fingerprint().to_string().len(), 64);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_validates() {
+        use crate::algebra::MODULUS;
+
+        let key_pair = KeyPair::new();
+        let mut json: serde_json::Value = serde_json::to_value(&key_pair).unwrap();
+        json["backwards"][4]["inner"][3] = 1.into();
+        let err = serde_json::from_value::<KeyPair>(json.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "key halves are not inverse to each other");
+
+        json["backwards"][4]["inner"][3] = MODULUS.into();
+        let err = serde_json::from_value::<KeyPair>(json.clone()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected an integer below 2^31 - 1"));
+
+        let json = serde_json::to_value(&Enc::encrypt(&key_pair, 1)).unwrap();
+        let mut short = json.clone();
+        short["inner"].as_array_mut().unwrap().pop();
+        let err = serde_json::from_value::<Enc>(short).unwrap_err();
+        assert!(err.to_string().contains("expected a sequence of 9 entries"));
+        let mut long = json;
+        long["inner"][0]["inner"]
+            .as_array_mut()
+            .unwrap()
+            .push(0.into());
+        let err = serde_json::from_value::<Enc>(long).unwrap_err();
+        assert!(err.to_string().contains("expected a sequence of 4 entries"));
+    }
+
+    #[quickcheck]
+    fn prop_field_division_homomorphic(x: u32, y: Mod231) -> TestResult {
+        if y.is_zero() {
+            return TestResult::discard();
+        }
+        let key_pair = KeyPair::new();
+        let enc = Enc::encrypt(&key_pair, x) * y.invert() + Mod231(1);
+        let expected = Mod231::from(x) / y + Mod231(1);
+        TestResult::from_bool(
+            enc.decrypt_residue(&key_pair) == expected
+                && enc.try_decrypt_residue(&key_pair).ok() == Some(expected),
+        )
+    }
+
+    #[test]
+    fn decrypt_mean_as_fraction() {
+        let key_pair = KeyPair::new();
+        let values = [7u32, 2, 9, 4, 1, 3];
+        let sum = values
+            .iter()
+            .map(|&x| Enc::encrypt(&key_pair, x))
+            .fold(Enc::zero(), |acc, x| acc + x);
+        let mean = sum * Mod231::from(values.len() as u32).invert();
+        assert_eq!(mean.decrypt_fraction(&key_pair), Some(Fraction::new(13, 3)));
+
+        let ratio = Enc::encrypt(&key_pair, 5) * -Mod231(7).invert();
+        assert_eq!(
+            ratio.decrypt_fraction(&key_pair).map(|f| f.to_string()),
+            Some("-5/7".to_string())
+        );
+    }
+
+    #[quickcheck]
+    fn prop_quaternion_homomorphic(a: Q231, b: Q231) -> bool {
+        let key_pair = KeyPair::new();
+        let (x, y) = (
+            Enc::encrypt_quaternion(&key_pair, a),
+            Enc::encrypt_quaternion(&key_pair, b),
+        );
+        (x * y + x).decrypt_quaternion(&key_pair) == a * b + a
+            && (y * x * 2).try_decrypt_quaternion(&key_pair).ok() == Some(b * a.scale(Mod231(2)))
+    }
+
+    #[test]
+    fn matrix_products_keep_order() {
+        let key_pair = KeyPair::new();
+        let m = |a: u32, b: u32, c: u32, d: u32| {
+            [
+                [Mod231::from(a), Mod231::from(b)],
+                [Mod231::from(c), Mod231::from(d)],
+            ]
+        };
+        let shear = Enc::encrypt_matrix(&key_pair, m(1, 2, 0, 1));
+        let swap = Enc::encrypt_matrix(&key_pair, m(0, 1, 1, 0));
+        assert_eq!((shear * swap).decrypt_matrix(&key_pair), m(2, 1, 1, 0));
+        assert_eq!((swap * shear).decrypt_matrix(&key_pair), m(0, 1, 1, 2));
+        assert_eq!((shear + 3).decrypt_matrix(&key_pair), m(4, 2, 0, 4));
+
+        assert!(matches!(swap.try_decrypt(&key_pair), Err(Error::NotScalar)));
+        assert!(matches!(
+            swap.try_decrypt_quaternion(&KeyPair::new()),
+            Err(Error::WrongKey)
+        ));
+        let scalar = Enc::encrypt(&key_pair, 5).decrypt_matrix(&key_pair);
+        assert_eq!(scalar, m(5, 0, 0, 5));
+    }
+
+    #[quickcheck]
+    fn prop_complex_homomorphic(a: C231, b: C231, c: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let (x, y) = (
+            Enc::encrypt_complex(&key_pair, a),
+            Enc::encrypt_complex(&key_pair, b),
+        );
+        let expected = a * b + a.conjugate() * C231::from(Mod231::from(c));
+        let conjugate = Enc::encrypt_complex(&key_pair, a.conjugate());
+        (x * y + conjugate * c).try_decrypt_complex(&key_pair).ok() == Some(expected)
+            && (x * y).decrypt_complex(&key_pair) == (y * x).decrypt_complex(&key_pair)
+    }
+
+    #[test]
+    fn complex_rotation() {
+        let key_pair = KeyPair::new();
+        let i = C231::new(Mod231(0), Mod231(1));
+        let z = Enc::encrypt_complex(&key_pair, C231::new(Mod231(3), Mod231(4)));
+        let rotated = z * Enc::encrypt_complex(&key_pair, i);
+        assert_eq!(
+            <(Mod231, Mod231)>::from(rotated.decrypt_complex(&key_pair)),
+            (-Mod231(4), Mod231(3))
+        );
+        assert_eq!(
+            Enc::encrypt(&key_pair, 5)
+                .try_decrypt_complex(&key_pair)
+                .ok(),
+            Some(C231::from(Mod231(5)))
+        );
+
+        let j = Q231::new(Mod231(0), Mod231(0), Mod231(1), Mod231(0));
+        let quaternion = Enc::encrypt_quaternion(&key_pair, j);
+        assert!(matches!(
+            quaternion.try_decrypt_complex(&key_pair),
+            Err(Error::NotComplex)
+        ));
+        assert!(matches!(
+            z.try_decrypt_complex(&KeyPair::new()),
+            Err(Error::WrongKey)
+        ));
+    }
+
+    #[quickcheck]
+    fn prop_try_decrypt_checks_key(x: u32, y: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let enc = Enc::encrypt(&key_pair, x) * Enc::encrypt(&key_pair, y) * 3 + 1;
+        let other = Enc::encrypt(&KeyPair::new(), x) * Enc::encrypt(&KeyPair::new(), y);
+        enc.try_decrypt(&key_pair).ok() == Some(enc.decrypt(&key_pair))
+            && matches!(other.try_decrypt(&key_pair), Err(Error::WrongKey))
+            && matches!((enc + 1).try_decrypt(&KeyPair::new()), Err(Error::WrongKey))
+    }
+
+    #[test]
+    fn try_dot_checks_length() {
+        let key_pair = KeyPair::new();
+        let xs = [Enc::encrypt(&key_pair, 2), Enc::encrypt(&key_pair, 3)];
+        let dot = Enc::try_dot(&xs, &xs).unwrap();
+        assert_eq!(dot.try_decrypt(&key_pair).unwrap(), 13);
+        assert!(matches!(
+            Enc::try_dot(&xs, &xs[1..]),
+            Err(Error::Length {
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn key_files_roundtrip() {
+        let path = std::env::temp_dir().join(format!("gmorph-key-{}", std::process::id()));
+        let key_pair = KeyPair::new();
+        key_pair.save(&path).unwrap();
+        assert_eq!(
+            KeyPair::load(&path).unwrap().fingerprint(),
+            key_pair.fingerprint()
+        );
+
+        fs::write(&path, &key_pair.to_bytes()[..]).unwrap();
+        assert_eq!(
+            KeyPair::load(&path).unwrap().fingerprint(),
+            key_pair.fingerprint()
+        );
+
+        fs::write(&path, "gmorph-key1:AAAA\n").unwrap();
+        assert!(matches!(KeyPair::load(&path), Err(Error::Parse(_))));
+        fs::write(&path, b"\x01\x02").unwrap();
+        assert!(matches!(KeyPair::load(&path), Err(Error::Decode(_))));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(KeyPair::load(&path), Err(Error::Io(_))));
+    }
 
     #[test]
     fn identity() {
@@ -178,6 +959,15 @@ mod tests {
         )
     }
 
+    #[test]
+    fn is_zero_only_detects_trivial_zero() {
+        let key_pair = KeyPair::new();
+        let zero = Enc::encrypt(&key_pair, 0);
+        assert!(Enc::zero().is_zero());
+        assert!(!zero.is_zero());
+        assert_eq!((zero + Enc::zero()).decrypt(&key_pair), 0);
+    }
+
     #[test]
     fn idempotence() {
         let key_pair = KeyPair::default();
@@ -214,4 +1004,36 @@ mod tests {
         let enc_y = Enc::encrypt(&key_pair, y);
         (enc_x + enc_y).decrypt(&key_pair) == x + y
     }
+
+    #[quickcheck]
+    fn prop_one_is_neutral(x: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let enc = Enc::encrypt(&key_pair, x);
+        Enc::one().decrypt(&key_pair) == 1 && enc * Enc::one() == enc
+    }
+
+    #[quickcheck]
+    fn prop_enc_dot_homomorphic(xs: Vec<(u32, u32)>) -> bool {
+        let key_pair = KeyPair::new();
+        let lhs: Vec<_> = xs.iter().map(|x| Enc::encrypt(&key_pair, x.0)).collect();
+        let rhs: Vec<_> = xs.iter().map(|x| Enc::encrypt(&key_pair, x.1)).collect();
+        let expected = xs.iter().fold(Mod231(0), |acc, x| {
+            acc + Mod231::from(x.0) * Mod231::from(x.1)
+        });
+        expected == Enc::dot(&lhs, &rhs).decrypt(&key_pair)
+    }
+
+    #[quickcheck]
+    fn prop_enc_scalar_add_homomorphic(x: u32, y: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let enc_x = Enc::encrypt(&key_pair, x);
+        (enc_x + y).decrypt(&key_pair) == (Mod231::from(x) + Mod231::from(y)).0
+    }
+
+    #[quickcheck]
+    fn prop_enc_scalar_mul_homomorphic(x: u32, y: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let enc_x = Enc::encrypt(&key_pair, x);
+        (enc_x * y).decrypt(&key_pair) == (Mod231::from(x) * Mod231::from(y)).0
+    }
 }
@@ -0,0 +1,132 @@
+//! Crate-wide error type
+//!
+//! Every fallible operation has a `try_` variant, or returns a `Result`
+//! in the first place, so that bad input never has to end in a panic.
+//! The module-specific errors (`DecodeError`, `ParseError`, `BoundError`)
+//! all convert into `Error`, so `?` works across them:
+//!
+//! ```
+//! use gmorph::*;
+//!
+//! fn sum(key_pair: &str, values: &[&str]) -> Result<u32, Error> {
+//!     let key_pair: KeyPair = key_pair.parse()?;
+//!     let mut acc = Enc::encrypt(&key_pair, 0);
+//!     for value in values {
+//!         acc += value.parse::<Enc>()?;
+//!     }
+//!     acc.try_decrypt(&key_pair)
+//! }
+//!
+//! let key_pair = KeyPair::default();
+//! let values = [
+//!     Enc::encrypt(&key_pair, 2).to_string(),
+//!     Enc::encrypt(&key_pair, 3).to_string(),
+//! ];
+//! let values: Vec<&str> = values.iter().map(|v| v.as_str()).collect();
+//! assert_eq!(5, sum(&key_pair.to_string(), &values).unwrap());
+//! assert!(sum(&KeyPair::default().to_string(), &values).is_err());
+//! assert!(sum("gmorph-key1:AAAA", &values).is_err());
+//! ```
+use super::armor::ParseError;
+use super::bounded::BoundError;
+use super::wire::DecodeError;
+use core::fmt;
+
+/// Errors raised by any operation of the crate
+#[derive(Debug)]
+pub enum Error {
+    /// Division by zero, or inversion of a non-invertible element
+    NotInvertible,
+    /// Operands of different length were combined
+    Length { expected: usize, found: usize },
+    /// The ciphertext does not decrypt under the given key: either it
+    /// was encrypted with another key, or it was altered
+    WrongKey,
+    /// The plaintext is a quaternion, not an element of the field
+    NotScalar,
+    /// The plaintext is a quaternion, not a complex number
+    NotComplex,
+    /// See `BoundError`
+    Bound(BoundError),
+    /// See `DecodeError`
+    Decode(DecodeError),
+    /// See `ParseError`
+    Parse(ParseError),
+    /// Reading or writing a file or a dataset failed, or a dataset
+    /// header is malformed
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotInvertible => write!(f, "element is not invertible"),
+            Error::Length { expected, found } => write!(
+                f,
+                "operands differ in length: expected {}, found {}",
+                expected, found
+            ),
+            Error::WrongKey => write!(f, "ciphertext does not match the key"),
+            Error::NotScalar => write!(f, "plaintext is a quaternion, not a field element"),
+            Error::NotComplex => write!(f, "plaintext is a quaternion, not a complex number"),
+            Error::Bound(e) => e.fmt(f),
+            Error::Decode(e) => e.fmt(f),
+            Error::Parse(e) => e.fmt(f),
+            #[cfg(feature = "std")]
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Fails with `Error::Length` unless `found` equals `expected`
+#[inline]
+pub(crate) fn check_length(expected: usize, found: usize) -> Result<(), Error> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(Error::Length { expected, found })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bound(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<BoundError> for Error {
+    #[inline]
+    fn from(e: BoundError) -> Self {
+        Error::Bound(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    #[inline]
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    #[inline]
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    #[inline]
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,56 @@
+//! Entry points for the fuzz targets in `fuzz/`
+//!
+//! Available with the `fuzzing` feature, and not part of the stable API.
+//! The modular reductions are private to the crate, so they are exposed
+//! here on plain integers, to be checked against naive `%`.
+use super::algebra::{self, Mod231};
+
+/// 2^31 - 1
+pub const MODULUS: u32 = algebra::MODULUS;
+
+/// Reduces any `u64` modulo 2^31 - 1
+#[inline]
+pub fn normalize_u64(v: u64) -> u32 {
+    algebra::normalize_u64(v)
+}
+
+/// Reduces any `u128` modulo 2^31 - 1
+#[inline]
+pub fn normalize_u128(v: u128) -> u32 {
+    algebra::normalize_u128(v)
+}
+
+/// Reduces a product of two residues modulo 2^31 - 1
+///
+/// Only defined for arguments up to `(MODULUS - 1)^2`.
+#[inline]
+pub fn normalize_product(v: u64) -> u32 {
+    algebra::normalize_product(v)
+}
+
+/// `a + b` in the field, reducing both arguments first
+#[inline]
+pub fn add(a: u32, b: u32) -> u32 {
+    (Mod231::from(a) + Mod231::from(b)).0
+}
+
+/// `a - b` in the field, reducing both arguments first
+#[inline]
+pub fn sub(a: u32, b: u32) -> u32 {
+    (Mod231::from(a) - Mod231::from(b)).0
+}
+
+/// `a * b` in the field, reducing both arguments first
+///
+/// Reduces the product with `normalize_product` under the
+/// `experimental-normalize` feature.
+#[inline]
+pub fn mul(a: u32, b: u32) -> u32 {
+    (Mod231::from(a) * Mod231::from(b)).0
+}
+
+/// `a / b` in the field, or `None` if `b` is divisible by the modulus
+#[inline]
+pub fn div(a: u32, b: u32) -> Option<u32> {
+    Mod231::from(a).try_div(Mod231::from(b)).ok().map(|x| x.0)
+}
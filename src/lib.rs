@@ -28,14 +28,56 @@
 //! You can find some more examples in [examples] folder.
 //!
 //! [examples]: https://github.com/golemfactory/gMorph/tree/master/examples
-mod algebra;
-pub mod enc;
+//!
+//! ## Features:
+//! * `std` (default) - everything that needs the standard library, most
+//!   notably encryption with `rand::thread_rng`; implies `alloc`
+//! * `alloc` - batches of ciphertexts (`batch` module)
+//! * `serde` (default) - `Serialize` and `Deserialize` for ciphertexts
+//!   and keys
+//! * `fuzzing` - internals used by the fuzz targets in `fuzz/`, not a
+//!   stable API
+//!
+//! Without `std` the crate is `no_std`: arithmetic and decryption work as
+//! usual, and encryption takes a caller-supplied RNG, see
+//! `Enc::encrypt_with_rng` and `KeyPair::generate`.
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[macro_use]
-extern crate alga_derive;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod algebra;
+pub mod armor;
+#[cfg(feature = "std")]
+pub mod backend;
+#[cfg(feature = "alloc")]
+pub mod batch;
+pub mod bounded;
+#[cfg(feature = "std")]
+pub mod cost;
+#[cfg(feature = "cryptanalysis")]
+pub mod cryptanalysis;
+#[cfg(feature = "std")]
+pub mod dataset;
+pub mod enc;
+pub mod error;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
+pub mod noise;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod rational;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+pub mod wire;
 
 #[cfg(test)]
 #[macro_use(quickcheck)]
 extern crate quickcheck_macros;
 
-pub use self::enc::{Decrypt, Enc, Encrypt, KeyPair};
+pub use self::armor::ParseError;
+pub use self::bounded::{BoundError, Bounded, Limit};
+pub use self::enc::{Decrypt, Enc, Encrypt, Fingerprint, KeyPair};
+pub use self::error::Error;
+pub use self::wire::DecodeError;
@@ -0,0 +1,238 @@
+//! Strategies for hiding a plaintext quaternion inside a noisy matrix
+//!
+//! Before being conjugated by the key, every plaintext is placed at
+//! position (0, 0) of a 3x3 quaternion matrix whose remaining entries are
+//! noise. The homomorphism survives as long as the first column below
+//! (0, 0) is zero: the (0, 0) entry of a sum or product of such matrices is
+//! then the sum or product of the (0, 0) entries. All strategies shipped
+//! here produce matrices of that shape, so ciphertexts created with
+//! different strategies can be freely combined.
+//!
+//! The matrix is always 3x3: keys, ciphertexts, batches and every
+//! serialized form are laid out for that size, so strategies differ in
+//! which of the six free positions carry noise and how, not in how many
+//! positions there are.
+use super::algebra::{invert_2x2, Invertible, Matrix2, Matrix3, Mod231, Q231};
+use num_traits::Zero;
+use rand::Rng;
+
+/// Way of embedding a plaintext quaternion in a noisy matrix
+///
+/// The trait can be implemented outside the crate, e.g. to try out a
+/// different noise layout:
+///
+/// ```
+/// use gmorph::algebra::{Matrix3, Mod231, Q231};
+/// use gmorph::noise::NoiseStrategy;
+/// use gmorph::*;
+/// use rand::Rng;
+///
+/// /// Random first row over a random scalar 2x2 block
+/// struct ScalarBlock;
+///
+/// impl NoiseStrategy for ScalarBlock {
+///     fn embed<R: Rng + ?Sized>(&self, value: Q231, rng: &mut R) -> Matrix3<Q231> {
+///         let scalar = Q231::from_real(rng.gen::<Mod231>());
+///         let mut noise = Matrix3::zeros();
+///         noise[(0, 0)] = value;
+///         noise[(0, 1)] = rng.gen();
+///         noise[(0, 2)] = rng.gen();
+///         noise[(1, 1)] = scalar;
+///         noise[(2, 2)] = scalar;
+///         noise
+///     }
+/// }
+///
+/// let key_pair = KeyPair::default();
+/// let x = Enc::encrypt_with_noise(&key_pair, 6, &ScalarBlock);
+/// let y = Enc::encrypt_with_noise(&key_pair, 7, &ScalarBlock);
+/// let given: u32 = (x * y + x).decrypt(&key_pair);
+/// assert_eq!(48, given);
+/// ```
+pub trait NoiseStrategy {
+    /// Returns a matrix with `value` at position (0, 0), and all other
+    /// entries of the first column equal to zero
+    fn embed<R: Rng + ?Sized>(&self, value: Q231, rng: &mut R) -> Matrix3<Q231>;
+}
+
+/// Random quaternion of norm zero
+///
+/// In split form the norm is the determinant, so norm-zero quaternions
+/// are the singular 2x2 matrices, all of which (but zero) are of the
+/// form `u * v^T`. Building one that way needs neither rejection
+/// sampling nor a square root.
+fn isotropic<R: Rng + ?Sized>(rng: &mut R) -> Q231 {
+    let (u0, u1, v0, v1) = rng.gen::<(Mod231, Mod231, Mod231, Mod231)>();
+    Q231::from_entries([u0 * v0, u0 * v1, u1 * v0, u1 * v1])
+}
+
+/// The default strategy: random upper triangle, with a norm-zero
+/// quaternion in the middle which makes the matrix non-invertible
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Triangular;
+
+impl NoiseStrategy for Triangular {
+    fn embed<R: Rng + ?Sized>(&self, value: Q231, rng: &mut R) -> Matrix3<Q231> {
+        let mut noise = Matrix3::<Q231>::zeros()
+            .map(|_| rng.gen::<Q231>())
+            .upper_triangle();
+        // Make sure the middle element (and hence the matrix) is noninvertible
+        noise[4] = isotropic(rng);
+        noise[0] = value;
+        noise
+    }
+}
+
+/// Random first row, and a random invertible 2x2 block in the
+/// lower-right corner
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FullRankBlock;
+
+impl NoiseStrategy for FullRankBlock {
+    fn embed<R: Rng + ?Sized>(&self, value: Q231, rng: &mut R) -> Matrix3<Q231> {
+        let block = loop {
+            let block = Matrix2::<Q231>::zeros().map(|_| rng.gen::<Q231>());
+            if invert_2x2(&block).is_some() {
+                break block;
+            }
+        };
+        let mut noise = Matrix3::<Q231>::zeros().map(|_| rng.gen::<Q231>());
+        for r in 0..2 {
+            for c in 0..2 {
+                noise[(r + 1, c + 1)] = block[(r, c)];
+            }
+        }
+        noise[0] = value;
+        noise[1] = Q231::zero();
+        noise[2] = Q231::zero();
+        noise
+    }
+}
+
+/// Random square-zero 2x2 matrix, `u * v^T` with `v^T * u = 0`
+fn square_zero<R: Rng + ?Sized>(rng: &mut R) -> Matrix2<Q231> {
+    let (u0, v0) = rng.gen::<(Q231, Q231)>();
+    let u1 = loop {
+        let u1 = rng.gen::<Q231>();
+        if u1.try_invert().is_some() {
+            break u1;
+        }
+    };
+    let v1 = -(v0 * u0 * u1.invert());
+    Matrix2::new(u0 * v0, u0 * v1, u1 * v0, u1 * v1)
+}
+
+/// Random first row, and a 2x2 block in the lower-right corner which is
+/// a random nilpotent matrix shifted by a random scalar
+///
+/// The shift is what hides the plaintext: the real part of the trace of
+/// a ciphertext is the plaintext plus that of the block, and the real
+/// part of the trace of a nilpotent matrix is always zero.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct NilpotentBlock;
+
+impl NoiseStrategy for NilpotentBlock {
+    fn embed<R: Rng + ?Sized>(&self, value: Q231, rng: &mut R) -> Matrix3<Q231> {
+        let block = square_zero(rng);
+        let shift = Q231::from_real(rng.gen());
+        let mut noise = Matrix3::<Q231>::zeros();
+        for r in 0..2 {
+            for c in 0..2 {
+                noise[(r + 1, c + 1)] = block[(r, c)];
+            }
+            noise[(0, r + 1)] = rng.gen();
+            noise[(r + 1, r + 1)] += shift;
+        }
+        noise[0] = value;
+        noise
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enc::{Decrypt, Enc, KeyPair};
+
+    fn enc_with<N: NoiseStrategy>(key_pair: &KeyPair, value: u32, noise: &N) -> Enc {
+        Enc::encrypt_with_noise(key_pair, value, noise)
+    }
+
+    fn homomorphic<N: NoiseStrategy>(noise: &N, x: u32, y: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let enc_x = enc_with(&key_pair, x, noise);
+        let enc_y = enc_with(&key_pair, y, noise);
+        let expected = Mod231::from(x) * Mod231::from(y) + Mod231::from(x);
+        expected == (enc_x * enc_y + enc_x).decrypt(&key_pair)
+    }
+
+    fn first_column_is_zero<N: NoiseStrategy>(noise: &N, x: Q231) -> bool {
+        let m = noise.embed(x, &mut rand::thread_rng());
+        m[0] == x && m[1].is_zero() && m[2].is_zero()
+    }
+
+    #[quickcheck]
+    fn prop_triangular_homomorphic(x: u32, y: u32) -> bool {
+        homomorphic(&Triangular, x, y)
+    }
+
+    #[quickcheck]
+    fn prop_full_rank_block_homomorphic(x: u32, y: u32) -> bool {
+        homomorphic(&FullRankBlock, x, y)
+    }
+
+    #[quickcheck]
+    fn prop_nilpotent_block_homomorphic(x: u32, y: u32) -> bool {
+        homomorphic(&NilpotentBlock, x, y)
+    }
+
+    #[quickcheck]
+    fn prop_strategies_compose(x: u32, y: u32, z: u32) -> bool {
+        let key_pair = KeyPair::new();
+        let enc_x = enc_with(&key_pair, x, &Triangular);
+        let enc_y = enc_with(&key_pair, y, &FullRankBlock);
+        let enc_z = enc_with(&key_pair, z, &NilpotentBlock);
+        let expected = (Mod231::from(x) + Mod231::from(y)) * Mod231::from(z);
+        expected == ((enc_x + enc_y) * enc_z).decrypt(&key_pair)
+    }
+
+    #[test]
+    fn isotropic_has_norm_zero() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            assert_eq!(isotropic(&mut rng).norm2(), Mod231(0));
+        }
+    }
+
+    #[test]
+    fn square_zero_squares_to_zero() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let m = square_zero(&mut rng);
+            assert!((m * m).is_zero());
+        }
+    }
+
+    #[cfg(feature = "cryptanalysis")]
+    #[test]
+    fn trace_does_not_reveal_plaintext() {
+        use crate::cryptanalysis::trace_invariant_with_noise;
+
+        let key_pair = KeyPair::new();
+        let reports = [
+            trace_invariant_with_noise(&key_pair, &Triangular, 50),
+            trace_invariant_with_noise(&key_pair, &FullRankBlock, 50),
+            trace_invariant_with_noise(&key_pair, &NilpotentBlock, 50),
+        ];
+        for report in reports.iter() {
+            assert!(report.invariant_holds, "{:?}", report);
+            assert_eq!(report.success_rate, 0.0, "{:?}", report);
+        }
+    }
+
+    #[quickcheck]
+    fn prop_first_column_is_zero(x: Q231) -> bool {
+        first_column_is_zero(&Triangular, x)
+            && first_column_is_zero(&FullRankBlock, x)
+            && first_column_is_zero(&NilpotentBlock, x)
+    }
+}
@@ -0,0 +1,107 @@
+//! Parallel encryption, decryption and reductions
+//!
+//! Available with the `rayon` feature. The work is spread over rayon's
+//! global thread pool, or over the pool the calls are `install`ed in.
+//!
+//! Results do not depend on the number of threads: arithmetic modulo
+//! 2^31 - 1 is exact, so regrouping a sum or a product by the tree
+//! reduction changes nothing, and products are only ever combined in
+//! their original order. Encryption stays randomized as usual, but the
+//! ciphertexts decrypt to the same values.
+//!
+//! ## Example:
+//!
+//! ```
+//! use gmorph::parallel::{par_encrypt, ParallelEnc};
+//! use gmorph::*;
+//! use rayon::prelude::*;
+//!
+//! let key_pair = KeyPair::default();
+//! let values: Vec<u32> = (1..10).collect();
+//! let enc = par_encrypt(&key_pair, &values);
+//! let given = enc.into_par_iter().par_sum().decrypt(&key_pair);
+//! let expected: u32 = (1..10).sum();
+//!
+//! assert_eq!(expected, given, "the sums should be equal, and equal to 45");
+//! ```
+use super::enc::{Decrypt, Enc, Encrypt, KeyPair};
+use num_traits::{One, Zero};
+use rayon::prelude::*;
+use std::ops::{Add, Mul};
+
+/// Encrypts every value in `values` using `key_pair`, in parallel
+pub fn par_encrypt(key_pair: &KeyPair, values: &[u32]) -> Vec<Enc> {
+    values
+        .par_iter()
+        .map(|&value| Enc::encrypt(key_pair, value))
+        .collect()
+}
+
+/// Decrypts every ciphertext in `encs` using `key_pair`, in parallel
+pub fn par_decrypt(key_pair: &KeyPair, encs: &[Enc]) -> Vec<u32> {
+    encs.par_iter().map(|enc| enc.decrypt(key_pair)).collect()
+}
+
+/// Tree reductions over parallel iterators of `Enc`
+pub trait ParallelEnc: ParallelIterator<Item = Enc> {
+    /// Homomorphic sum of all ciphertexts, `Enc::zero()` if there are none
+    fn par_sum(self) -> Enc {
+        self.reduce(Enc::zero, Add::add)
+    }
+
+    /// Homomorphic product of all ciphertexts in iteration order,
+    /// `Enc::one()` if there are none
+    ///
+    /// Requires an indexed iterator, since products of ciphertexts do not
+    /// commute and must be combined in order.
+    fn par_product(self) -> Enc
+    where
+        Self: IndexedParallelIterator,
+    {
+        self.reduce(Enc::one, Mul::mul)
+    }
+}
+
+impl<I: ParallelIterator<Item = Enc>> ParallelEnc for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::Mod231;
+    use rayon::ThreadPoolBuilder;
+
+    fn in_pool<T: Send>(threads: usize, f: impl FnOnce() -> T + Send) -> T {
+        ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(f)
+    }
+
+    #[quickcheck]
+    fn prop_par_encrypt_decrypt(values: Vec<u32>) -> bool {
+        let key_pair = KeyPair::new();
+        let decrypted = par_decrypt(&key_pair, &par_encrypt(&key_pair, &values));
+        decrypted
+            .iter()
+            .zip(&values)
+            .all(|(&x, &v)| Mod231::from(v) == x)
+    }
+
+    #[quickcheck]
+    fn prop_reductions_match_serial(encs: Vec<Enc>) -> bool {
+        let sum = encs.iter().fold(Enc::zero(), |acc, &x| acc + x);
+        let product = encs.iter().fold(Enc::one(), |acc, &x| acc * x);
+        encs.par_iter().copied().par_sum() == sum
+            && encs.par_iter().copied().par_product() == product
+    }
+
+    #[quickcheck]
+    fn prop_reductions_independent_of_threads(encs: Vec<Enc>) -> bool {
+        let reduce = || {
+            let encs = encs.par_iter().copied().with_max_len(1);
+            (encs.clone().par_sum(), encs.par_product())
+        };
+        in_pool(1, reduce) == in_pool(4, reduce)
+    }
+}
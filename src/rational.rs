@@ -0,0 +1,235 @@
+//! Rational reconstruction of decrypted field elements
+//!
+//! The plaintext space is the field GF(2^31 - 1), so dividing an encrypted
+//! value by a plaintext constant is just multiplying it by the inverse
+//! of that constant. The result decrypts to a residue `x = n / d`, from
+//! which the fraction `n / d` can be recovered, provided its numerator
+//! and denominator are small: if `|n| <= N`, `0 < d <= D` and
+//! `2 * N * D < 2^31 - 1`, the fraction is the only one within these
+//! bounds which reduces to `x`, and the extended Euclidean algorithm
+//! finds it.
+//!
+//! ## Example:
+//!
+//! ```
+//! use gmorph::algebra::{Invertible, Mod231};
+//! use gmorph::rational::Fraction;
+//! use gmorph::*;
+//!
+//! let key_pair = KeyPair::default();
+//! let values = [3, 4, 4, 5];
+//! let sum = values
+//!     .iter()
+//!     .fold(Enc::encrypt(&key_pair, 0), |acc, &x| acc + Enc::encrypt(&key_pair, x));
+//! let mean = sum * Mod231::from(values.len() as u32).invert();
+//!
+//! assert_eq!(
+//!     mean.decrypt_fraction(&key_pair),
+//!     Some(Fraction::new(4, 1))
+//! );
+//! let third = Enc::encrypt(&key_pair, 1) * Mod231::from(3).invert();
+//! assert_eq!(third.decrypt_fraction(&key_pair).unwrap().to_string(), "1/3");
+//! ```
+use super::algebra::{Mod231, MODULUS};
+use super::error::Error;
+use core::fmt;
+
+/// Largest numerator and denominator recovered by `reconstruct`, the
+/// largest `N` with `2 * N^2 < 2^31 - 1`
+pub const BALANCED_BOUND: u32 = 32767;
+
+/// Fraction in lowest terms with a positive denominator
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Fraction {
+    numerator: i32,
+    denominator: u32,
+}
+
+impl Fraction {
+    /// Creates the fraction `numerator / denominator`, reduced to
+    /// lowest terms
+    ///
+    /// Panics if `denominator` is zero.
+    pub fn new(numerator: i32, denominator: u32) -> Self {
+        assert!(denominator != 0, "fraction with zero denominator");
+        let gcd = gcd(numerator.unsigned_abs(), denominator);
+        Self {
+            numerator: (i64::from(numerator) / i64::from(gcd)) as i32,
+            denominator: denominator / gcd,
+        }
+    }
+
+    #[inline]
+    pub fn numerator(&self) -> i32 {
+        self.numerator
+    }
+
+    #[inline]
+    pub fn denominator(&self) -> u32 {
+        self.denominator
+    }
+
+    /// Nearest `f64`
+    #[inline]
+    pub fn to_f64(self) -> f64 {
+        f64::from(self.numerator) / f64::from(self.denominator)
+    }
+
+    /// Reduction of the fraction modulo 2^31 - 1, the inverse of
+    /// `reconstruct`
+    ///
+    /// Fails if the denominator is a multiple of the modulus.
+    pub fn to_residue(self) -> Result<Mod231, Error> {
+        let numerator = Mod231::from(self.numerator.unsigned_abs());
+        let numerator = if self.numerator < 0 {
+            -numerator
+        } else {
+            numerator
+        };
+        numerator.try_div(Mod231::from(self.denominator))
+    }
+}
+
+impl fmt::Display for Fraction {
+    /// Writes `n/d`, or just `n` for integers
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// Recovers the fraction with numerator and denominator of magnitude at
+/// most `BALANCED_BOUND` which reduces to `x`, if there is one
+#[inline]
+pub fn reconstruct(x: Mod231) -> Option<Fraction> {
+    reconstruct_with_bounds(x, BALANCED_BOUND, BALANCED_BOUND)
+}
+
+/// Recovers the fraction `n / d` with `|n| <= numerator_bound` and
+/// `0 < d <= denominator_bound` which reduces to `x`, if there is one
+///
+/// Trading one bound for the other suits data with a known shape, e.g.
+/// means of up to 100 values use a `denominator_bound` of 100. Returns
+/// `None` if `2 * numerator_bound * denominator_bound` is not below the
+/// modulus, since the fraction would not be unique.
+pub fn reconstruct_with_bounds(
+    x: Mod231,
+    numerator_bound: u32,
+    denominator_bound: u32,
+) -> Option<Fraction> {
+    if 2 * u64::from(numerator_bound) * u64::from(denominator_bound) >= u64::from(MODULUS) {
+        return None;
+    }
+    // Invariant: r_i = t_i * x (mod MODULUS), with |t_i| growing as
+    // r_i shrinks. The first remainder within the numerator bound gives
+    // the fraction with the smallest denominator.
+    let (mut r0, mut r1) = (i64::from(MODULUS), i64::from(x.value()));
+    let (mut t0, mut t1) = (0i64, 1i64);
+    while r1 > i64::from(numerator_bound) {
+        let q = r0 / r1;
+        let r = r0 - q * r1;
+        let t = t0 - q * t1;
+        r0 = r1;
+        r1 = r;
+        t0 = t1;
+        t1 = t;
+    }
+    if t1 == 0 || t1.unsigned_abs() > u64::from(denominator_bound) {
+        return None;
+    }
+    let (numerator, denominator) = if t1 < 0 { (-r1, -t1) } else { (r1, t1) };
+    if gcd(r1 as u32, denominator as u32) != 1 {
+        return None;
+    }
+    Some(Fraction {
+        numerator: numerator as i32,
+        denominator: denominator as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::Invertible;
+    use quickcheck::TestResult;
+
+    #[quickcheck]
+    fn prop_reconstruct_roundtrip(numerator: i16, denominator: u16) -> TestResult {
+        if denominator == 0 || numerator == i16::MIN {
+            return TestResult::discard();
+        }
+        let fraction = Fraction::new(numerator.into(), denominator.into());
+        if fraction.denominator() > BALANCED_BOUND {
+            return TestResult::discard();
+        }
+        TestResult::from_bool(reconstruct(fraction.to_residue().unwrap()) == Some(fraction))
+    }
+
+    #[quickcheck]
+    fn prop_reconstruct_with_bounds(numerator: i32, denominator: u8) -> TestResult {
+        if denominator == 0 || numerator.unsigned_abs() > MODULUS / 512 {
+            return TestResult::discard();
+        }
+        let fraction = Fraction::new(numerator, denominator.into());
+        let found = reconstruct_with_bounds(fraction.to_residue().unwrap(), MODULUS / 512, 255);
+        TestResult::from_bool(found == Some(fraction))
+    }
+
+    #[quickcheck]
+    fn prop_reconstructed_fraction_reduces_to_input(x: Mod231) -> bool {
+        match reconstruct(x) {
+            Some(fraction) => {
+                fraction.to_residue().ok() == Some(x)
+                    && fraction.numerator().unsigned_abs() <= BALANCED_BOUND
+                    && fraction.denominator() <= BALANCED_BOUND
+            }
+            None => true,
+        }
+    }
+
+    #[test]
+    fn reconstruct_edge_cases() {
+        assert_eq!(reconstruct(Mod231::from(0)), Some(Fraction::new(0, 1)));
+        assert_eq!(reconstruct(-Mod231::from(1)), Some(Fraction::new(-1, 1)));
+        assert_eq!(
+            reconstruct(Mod231::from(2).invert()),
+            Some(Fraction::new(1, 2))
+        );
+        let bound = Mod231::from(BALANCED_BOUND);
+        assert_eq!(
+            reconstruct(-bound * (bound - Mod231::from(1)).invert()),
+            Some(Fraction::new(-(BALANCED_BOUND as i32), BALANCED_BOUND - 1))
+        );
+        // 40000 is out of the balanced range, but fine with a larger
+        // numerator bound
+        assert_eq!(reconstruct(Mod231::from(40000)), None);
+        assert_eq!(
+            reconstruct_with_bounds(Mod231::from(40000), 50000, 100),
+            Some(Fraction::new(40000, 1))
+        );
+        assert_eq!(
+            reconstruct_with_bounds(Mod231::from(1), 1 << 16, 1 << 15),
+            None
+        );
+    }
+
+    #[test]
+    fn fraction_display() {
+        assert_eq!(Fraction::new(6, 4).to_string(), "3/2");
+        assert_eq!(Fraction::new(-6, 3).to_string(), "-2");
+        assert_eq!(Fraction::new(-1, 4).to_f64(), -0.25);
+        assert!(Fraction::new(1, MODULUS).to_residue().is_err());
+    }
+}
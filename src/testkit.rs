@@ -0,0 +1,148 @@
+//! Reusable property checks for homomorphic types
+//!
+//! Available with the `testkit` feature. The properties are plain generic
+//! functions, so they can be fed to `quickcheck` directly, or run through
+//! the `check_*` helpers which instantiate them and panic on the first
+//! counterexample. Any type wrapping `Enc` can be checked either through
+//! its ring laws, or by implementing `HomomorphicBackend` for it.
+//!
+//! ## Example:
+//!
+//! ```
+//! use gmorph::backend::{GMorph, Plain};
+//! use gmorph::testkit;
+//! use gmorph::Enc;
+//!
+//! testkit::check_ring_laws::<Enc>();
+//! testkit::check_serde_roundtrip::<Enc>();
+//! testkit::check_backend::<Plain>();
+//! testkit::check_backend::<GMorph>();
+//! testkit::check_wrong_key::<GMorph>();
+//! ```
+use super::algebra::Mod231;
+use super::backend::HomomorphicBackend;
+use quickcheck::{Arbitrary, QuickCheck};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::ops::{Add, Mul};
+
+/// `a + b == b + a`
+pub fn add_commutative<T>(a: T, b: T) -> bool
+where
+    T: Copy + PartialEq + Add<Output = T>,
+{
+    a + b == b + a
+}
+
+/// `(a + b) + c == a + (b + c)`
+pub fn add_associative<T>(a: T, b: T, c: T) -> bool
+where
+    T: Copy + PartialEq + Add<Output = T>,
+{
+    (a + b) + c == a + (b + c)
+}
+
+/// `(a * b) * c == a * (b * c)`
+pub fn mul_associative<T>(a: T, b: T, c: T) -> bool
+where
+    T: Copy + PartialEq + Mul<Output = T>,
+{
+    (a * b) * c == a * (b * c)
+}
+
+/// `a * (b + c) == a * b + a * c` and `(a + b) * c == a * c + b * c`
+pub fn distributive<T>(a: T, b: T, c: T) -> bool
+where
+    T: Copy + PartialEq + Add<Output = T> + Mul<Output = T>,
+{
+    a * (b + c) == a * b + a * c && (a + b) * c == a * c + b * c
+}
+
+/// Serializing to JSON and back yields the original value
+pub fn serde_roundtrip<T>(value: T) -> bool
+where
+    T: PartialEq + Serialize + DeserializeOwned,
+{
+    serde_json::to_string(&value)
+        .and_then(|s| serde_json::from_str::<T>(&s))
+        .map(|v| v == value)
+        .unwrap_or(false)
+}
+
+/// Decrypting an encrypted value gives back the value modulo 2^31 - 1
+pub fn encrypt_roundtrip<B: HomomorphicBackend>(x: u32) -> bool {
+    let key = B::generate_key();
+    Mod231::from(x) == B::decrypt(&key, &B::encrypt(&key, x))
+}
+
+/// Decryption is additive
+pub fn add_homomorphic<B: HomomorphicBackend>(x: u32, y: u32) -> bool {
+    let key = B::generate_key();
+    let sum = B::add(&B::encrypt(&key, x), &B::encrypt(&key, y));
+    Mod231::from(x) + Mod231::from(y) == B::decrypt(&key, &sum)
+}
+
+/// Decryption is multiplicative
+pub fn mul_homomorphic<B: HomomorphicBackend>(x: u32, y: u32) -> bool {
+    let key = B::generate_key();
+    let product = B::mul(&B::encrypt(&key, x), &B::encrypt(&key, y));
+    Mod231::from(x) * Mod231::from(y) == B::decrypt(&key, &product)
+}
+
+/// Adding a plaintext constant adds it to the decrypted value
+pub fn scalar_add_homomorphic<B: HomomorphicBackend>(x: u32, y: u32) -> bool {
+    let key = B::generate_key();
+    let sum = B::add_scalar(&B::encrypt(&key, x), y);
+    Mod231::from(x) + Mod231::from(y) == B::decrypt(&key, &sum)
+}
+
+/// Multiplying by a plaintext constant multiplies the decrypted value
+pub fn scalar_mul_homomorphic<B: HomomorphicBackend>(x: u32, y: u32) -> bool {
+    let key = B::generate_key();
+    let product = B::mul_scalar(&B::encrypt(&key, x), y);
+    Mod231::from(x) * Mod231::from(y) == B::decrypt(&key, &product)
+}
+
+/// Decrypting with an unrelated key does not reveal the value
+///
+/// Only meaningful for schemes which actually hide their inputs; the
+/// property fails with overwhelming probability on the `Plain` backend.
+pub fn wrong_key<B: HomomorphicBackend>(x: u32) -> bool {
+    let key = B::generate_key();
+    let other = B::generate_key();
+    Mod231::from(x) != B::decrypt(&other, &B::encrypt(&key, x))
+}
+
+/// Checks the ring laws on arbitrary values of `T`
+pub fn check_ring_laws<T>()
+where
+    T: Arbitrary + Debug + Copy + PartialEq + Add<Output = T> + Mul<Output = T>,
+{
+    QuickCheck::new().quickcheck(add_commutative::<T> as fn(T, T) -> bool);
+    QuickCheck::new().quickcheck(add_associative::<T> as fn(T, T, T) -> bool);
+    QuickCheck::new().quickcheck(mul_associative::<T> as fn(T, T, T) -> bool);
+    QuickCheck::new().quickcheck(distributive::<T> as fn(T, T, T) -> bool);
+}
+
+/// Checks that arbitrary values of `T` survive a serialization round-trip
+pub fn check_serde_roundtrip<T>()
+where
+    T: Arbitrary + Debug + PartialEq + Serialize + DeserializeOwned,
+{
+    QuickCheck::new().quickcheck(serde_roundtrip::<T> as fn(T) -> bool);
+}
+
+/// Checks the homomorphism laws of backend `B`
+pub fn check_backend<B: HomomorphicBackend>() {
+    QuickCheck::new().quickcheck(encrypt_roundtrip::<B> as fn(u32) -> bool);
+    QuickCheck::new().quickcheck(add_homomorphic::<B> as fn(u32, u32) -> bool);
+    QuickCheck::new().quickcheck(mul_homomorphic::<B> as fn(u32, u32) -> bool);
+    QuickCheck::new().quickcheck(scalar_add_homomorphic::<B> as fn(u32, u32) -> bool);
+    QuickCheck::new().quickcheck(scalar_mul_homomorphic::<B> as fn(u32, u32) -> bool);
+}
+
+/// Checks that backend `B` does not decrypt under the wrong key
+pub fn check_wrong_key<B: HomomorphicBackend>() {
+    QuickCheck::new().quickcheck(wrong_key::<B> as fn(u32) -> bool);
+}
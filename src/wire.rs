@@ -0,0 +1,475 @@
+//! Compact binary encoding of ciphertexts
+//!
+//! An `Enc` takes `ENC_LEN` bytes on the wire, laid out as
+//!
+//! ```text
+//! offset  size  field
+//!      0     1  format version, currently 1
+//!      1     4  modulus, little-endian u32 (2^31 - 1)
+//!      5     1  dimension of the ciphertext matrix (3)
+//!      6   140  36 coefficients, 31 bits each
+//! ```
+//!
+//! The coefficients are the quaternion entries of the ciphertext matrix in
+//! column-major order, each as `w, i, j, k`. They are packed into one
+//! little-endian bit stream: coefficient `n` occupies bits `31 * n` up to
+//! `31 * n + 30`, where bit `b` is bit `b % 8` of byte `b / 8`. The four
+//! bits left over at the end are zero.
+//!
+//! A `KeyPair` takes `KEY_LEN` bytes: its two matrices, each encoded
+//! like a ciphertext.
+//!
+//! The encoding depends only on the values of the coefficients, so it does
+//! not change when the in-memory representation does. Decoding accepts only
+//! the canonical form: every coefficient has to be reduced modulo 2^31 - 1.
+//!
+//! Use `compact` with `#[serde(with = "gmorph::wire::compact")]` to
+//! serialize `Enc` fields in this format, and `EncArray` to work on
+//! ciphertexts stored back to back, for instance in a memory-mapped file,
+//! without copying them out first.
+use super::algebra::{normalize_u64, Mod231, MODULUS};
+use super::enc::Enc;
+use super::error::{check_length, Error};
+use core::fmt;
+
+/// Version of the format written by `Enc::to_bytes`
+pub const VERSION: u8 = 1;
+
+/// Dimension of the ciphertext matrix
+pub const DIMENSION: u8 = 3;
+
+/// Number of field coefficients in a ciphertext
+pub(crate) const COEFFICIENTS: usize = 36;
+
+const HEADER_LEN: usize = 6;
+const BITS: usize = 31;
+
+/// Length of an encoded `Enc` in bytes
+pub const ENC_LEN: usize = HEADER_LEN + (COEFFICIENTS * BITS).div_ceil(8);
+
+/// Length of an encoded `KeyPair` in bytes
+pub const KEY_LEN: usize = 2 * ENC_LEN;
+
+/// Errors raised when decoding a ciphertext
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input is not `ENC_LEN` bytes long
+    Length { expected: usize, found: usize },
+    /// The input was written by an unknown version of the format
+    Version(u8),
+    /// The input was encrypted modulo a different prime
+    Modulus(u32),
+    /// The input holds a matrix of a different size
+    Dimension(u8),
+    /// The coefficient at the given position is not reduced modulo
+    /// 2^31 - 1
+    Coefficient(usize),
+    /// The padding bits after the last coefficient are not zero
+    Padding,
+    /// The given number of bytes is left over after the last of
+    /// several ciphertexts
+    Trailing(usize),
+    /// The two halves of a key are not inverse to each other
+    NotInverse,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Length { expected, found } => write!(
+                f,
+                "ciphertext takes {} bytes, but {} were given",
+                expected, found
+            ),
+            DecodeError::Version(v) => write!(f, "unsupported format version {}", v),
+            DecodeError::Modulus(m) => write!(f, "unsupported modulus {}", m),
+            DecodeError::Dimension(d) => write!(f, "unsupported dimension {}", d),
+            DecodeError::Coefficient(n) => write!(f, "coefficient {} is out of range", n),
+            DecodeError::Padding => write!(f, "padding bits are not zero"),
+            DecodeError::Trailing(n) => {
+                write!(f, "{} bytes left over after the last ciphertext", n)
+            }
+            DecodeError::NotInverse => write!(f, "key halves are not inverse to each other"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// Writes the header and packs `coefficients` after it
+pub(crate) fn encode(coefficients: &[Mod231; COEFFICIENTS]) -> [u8; ENC_LEN] {
+    let mut out = [0u8; ENC_LEN];
+    out[0] = VERSION;
+    out[1..5].copy_from_slice(&MODULUS.to_le_bytes());
+    out[5] = DIMENSION;
+
+    let body = &mut out[HEADER_LEN..];
+    let mut acc = 0u64;
+    let mut filled = 0;
+    let mut pos = 0;
+    for x in coefficients.iter() {
+        acc |= u64::from(x.0) << filled;
+        filled += BITS;
+        while filled >= 8 {
+            body[pos] = acc as u8;
+            acc >>= 8;
+            filled -= 8;
+            pos += 1;
+        }
+    }
+    if filled > 0 {
+        body[pos] = acc as u8;
+    }
+    out
+}
+
+/// Checks the header of `bytes` and unpacks the coefficients
+pub(crate) fn decode(bytes: &[u8]) -> Result<[Mod231; COEFFICIENTS], DecodeError> {
+    if bytes.len() != ENC_LEN {
+        return Err(DecodeError::Length {
+            expected: ENC_LEN,
+            found: bytes.len(),
+        });
+    }
+    if bytes[0] != VERSION {
+        return Err(DecodeError::Version(bytes[0]));
+    }
+    let modulus = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+    if modulus != MODULUS {
+        return Err(DecodeError::Modulus(modulus));
+    }
+    if bytes[5] != DIMENSION {
+        return Err(DecodeError::Dimension(bytes[5]));
+    }
+
+    let mut body = bytes[HEADER_LEN..].iter();
+    let mut out = [Mod231(0); COEFFICIENTS];
+    let mut acc = 0u64;
+    let mut filled = 0;
+    for (n, x) in out.iter_mut().enumerate() {
+        while filled < BITS {
+            // the length check above guarantees enough bytes
+            acc |= u64::from(*body.next().unwrap()) << filled;
+            filled += 8;
+        }
+        let value = (acc & u64::from(MODULUS)) as u32;
+        if value == MODULUS {
+            return Err(DecodeError::Coefficient(n));
+        }
+        *x = Mod231(value);
+        acc >>= BITS;
+        filled -= BITS;
+    }
+    if acc != 0 {
+        return Err(DecodeError::Padding);
+    }
+    Ok(out)
+}
+
+/// Read-only view of ciphertexts encoded back to back
+///
+/// Each ciphertext is decoded only when it is read, and `sum` adds the
+/// ciphertexts up straight from their encoded coefficients, so the bytes
+/// can come from a memory-mapped file which is never loaded as a whole.
+#[derive(Debug, Copy, Clone)]
+pub struct EncArray<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> EncArray<'a> {
+    /// Views `bytes` as a sequence of ciphertexts of `ENC_LEN` bytes
+    ///
+    /// Only the length is checked here; the ciphertexts themselves are
+    /// checked as they are read.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        match bytes.len() % ENC_LEN {
+            0 => Ok(Self { bytes }),
+            n => Err(DecodeError::Trailing(n)),
+        }
+    }
+
+    /// Number of ciphertexts
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len() / ENC_LEN
+    }
+
+    /// Returns `true` if the view holds no ciphertexts
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Encoded bytes of the ciphertext at `index`
+    #[inline]
+    pub fn bytes(&self, index: usize) -> Option<&'a [u8]> {
+        self.bytes.get(index * ENC_LEN..(index + 1) * ENC_LEN)
+    }
+
+    /// Decodes the ciphertext at `index`
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Result<Enc, DecodeError>> {
+        self.bytes(index).map(Enc::from_bytes)
+    }
+
+    /// Iterates over the decoded ciphertexts
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = Result<Enc, DecodeError>> + 'a {
+        self.bytes.chunks_exact(ENC_LEN).map(Enc::from_bytes)
+    }
+
+    /// Sub-view of the ciphertexts in `start..end`
+    ///
+    /// Panics if the range is out of bounds.
+    #[inline]
+    pub fn slice(&self, start: usize, end: usize) -> Self {
+        Self {
+            bytes: &self.bytes[start * ENC_LEN..end * ENC_LEN],
+        }
+    }
+
+    /// Sum of all the ciphertexts
+    ///
+    /// The coefficients are accumulated as integers straight from the
+    /// encoding and reduced only once, without building intermediate
+    /// `Enc` values.
+    pub fn sum(&self) -> Result<Enc, DecodeError> {
+        // each coefficient is below 2^31, so 2^32 of them fit in a u64
+        const CHUNK: usize = 1 << 30;
+
+        let mut total = [Mod231(0); COEFFICIENTS];
+        let chunk_len = CHUNK.min(usize::MAX / ENC_LEN) * ENC_LEN;
+        for chunk in self.bytes.chunks(chunk_len) {
+            let mut acc = [0u64; COEFFICIENTS];
+            for bytes in chunk.chunks_exact(ENC_LEN) {
+                for (a, x) in acc.iter_mut().zip(decode(bytes)?.iter()) {
+                    *a += u64::from(x.0);
+                }
+            }
+            for (t, a) in total.iter_mut().zip(acc.iter()) {
+                *t += Mod231(normalize_u64(*a));
+            }
+        }
+        Ok(Enc::from_coefficients(&total))
+    }
+
+    /// Computes the sum of `self[i] * rhs[i]`, like `Enc::dot`
+    ///
+    /// Fails with `Error::Length` if the views differ in length, and with
+    /// `Error::Decode` on the first ciphertext which does not decode.
+    pub fn dot(&self, rhs: &EncArray) -> Result<Enc, Error> {
+        check_length(self.len(), rhs.len())?;
+        let mut error = None;
+        let pairs = self.iter().zip(rhs.iter()).map_while(|pair| match pair {
+            (Ok(a), Ok(b)) => Some((a, b)),
+            (Err(e), _) | (_, Err(e)) => {
+                error = Some(e);
+                None
+            }
+        });
+        let out = Enc::dot_iter(pairs);
+        error.map_or(Ok(out), |e| Err(e.into()))
+    }
+}
+
+/// Serde adapter writing `Enc` in the compact binary format
+///
+/// Formats with native byte strings (CBOR, bincode) store the
+/// `ENC_LEN` bytes as they are; text formats such as JSON fall back to a
+/// sequence of numbers.
+///
+/// ## Example:
+///
+/// ```
+/// use gmorph::*;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Payload {
+///     #[serde(with = "gmorph::wire::compact")]
+///     value: Enc,
+/// }
+///
+/// let key_pair = KeyPair::default();
+/// let payload = Payload { value: Enc::encrypt(&key_pair, 7) };
+/// let cbor = serde_cbor::to_vec(&payload).unwrap();
+/// let payload: Payload = serde_cbor::from_slice(&cbor).unwrap();
+///
+/// assert_eq!(7, payload.value.decrypt(&key_pair));
+/// ```
+#[cfg(feature = "serde")]
+pub mod compact {
+    use super::{DecodeError, Enc, ENC_LEN};
+    use core::fmt;
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(enc: &Enc, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&enc.to_bytes())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Enc, D::Error> {
+        deserializer.deserialize_bytes(Bytes)
+    }
+
+    struct Bytes;
+
+    impl<'de> Visitor<'de> for Bytes {
+        type Value = Enc;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} bytes of an encoded ciphertext", ENC_LEN)
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Enc, E> {
+            Enc::from_bytes(v).map_err(E::custom)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Enc, A::Error> {
+            let mut bytes = [0u8; ENC_LEN];
+            let mut len = 0;
+            while let Some(b) = seq.next_element::<u8>()? {
+                if len == ENC_LEN {
+                    let found = len + 1 + seq.size_hint().unwrap_or(0);
+                    return Err(A::Error::custom(DecodeError::Length {
+                        expected: ENC_LEN,
+                        found,
+                    }));
+                }
+                bytes[len] = b;
+                len += 1;
+            }
+            self.visit_bytes(&bytes[..len])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enc::{Encrypt, KeyPair};
+    use crate::Decrypt;
+    use num_traits::Zero;
+
+    #[test]
+    fn layout() {
+        let mut coefficients = [Mod231(0); COEFFICIENTS];
+        coefficients[0] = Mod231(MODULUS - 1);
+        coefficients[1] = Mod231(1);
+        coefficients[35] = Mod231(1 << 30);
+        let bytes = encode(&coefficients);
+
+        assert_eq!(ENC_LEN, 146);
+        assert_eq!(bytes[..6], [1, 0xff, 0xff, 0xff, 0x7f, 3]);
+        // 2^31 - 2 in bits 0..=30, then the second coefficient at bit 31
+        assert_eq!(bytes[6..11], [0xfe, 0xff, 0xff, 0xff, 0x00]);
+        // bit 30 of the last coefficient is bit 1115 of the stream
+        assert_eq!(bytes[145], 0x08);
+        assert_eq!(decode(&bytes), Ok(coefficients));
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        let bytes = encode(&[Mod231(5); COEFFICIENTS]);
+        let with = |i: usize, b: u8| {
+            let mut bytes = bytes;
+            bytes[i] = b;
+            decode(&bytes)
+        };
+
+        assert_eq!(
+            decode(&bytes[1..]),
+            Err(DecodeError::Length {
+                expected: ENC_LEN,
+                found: ENC_LEN - 1
+            })
+        );
+        assert_eq!(with(0, 2), Err(DecodeError::Version(2)));
+        assert_eq!(with(1, 0xfe), Err(DecodeError::Modulus(MODULUS - 1)));
+        assert_eq!(with(5, 4), Err(DecodeError::Dimension(4)));
+        assert_eq!(with(145, 0x10), Err(DecodeError::Padding));
+
+        let mut unreduced = bytes;
+        unreduced[6..10].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(decode(&unreduced), Err(DecodeError::Coefficient(0)));
+    }
+
+    #[quickcheck]
+    fn prop_bytes_roundtrip(enc: Enc) -> bool {
+        Enc::from_bytes(&enc.to_bytes()) == Ok(enc)
+    }
+
+    #[quickcheck]
+    fn prop_array_matches_decoded(values: Vec<(u32, u32)>) -> bool {
+        let key_pair = KeyPair::new();
+        let encrypt = |x| Enc::encrypt(&key_pair, x);
+        let lhs: Vec<_> = values.iter().map(|x| encrypt(x.0)).collect();
+        let rhs: Vec<_> = values.iter().map(|x| encrypt(x.1)).collect();
+        let to_bytes = |v: &[Enc]| -> Vec<u8> { v.iter().flat_map(|x| x.to_bytes()).collect() };
+        let (lhs_bytes, rhs_bytes) = (to_bytes(&lhs), to_bytes(&rhs));
+        let lhs_array = EncArray::new(&lhs_bytes).unwrap();
+        let rhs_array = EncArray::new(&rhs_bytes).unwrap();
+
+        let decoded: Vec<_> = lhs_array.iter().map(Result::unwrap).collect();
+        let sum = lhs.iter().fold(Enc::zero(), |acc, x| acc + *x);
+        lhs_array.len() == values.len()
+            && decoded == lhs
+            && lhs_array.sum() == Ok(sum)
+            && rhs_array.dot(&lhs_array).ok() == Some(Enc::dot(&rhs, &lhs))
+    }
+
+    #[test]
+    fn array_reports_bad_records() {
+        let key_pair = KeyPair::new();
+        let mut bytes: Vec<u8> = (0..3)
+            .flat_map(|x| Enc::encrypt(&key_pair, x).to_bytes())
+            .collect();
+        assert_eq!(
+            EncArray::new(&bytes[1..]).unwrap_err(),
+            DecodeError::Trailing(ENC_LEN - 1)
+        );
+
+        bytes[ENC_LEN] = 2;
+        let array = EncArray::new(&bytes).unwrap();
+        assert_eq!(array.get(0).unwrap().unwrap().decrypt(&key_pair), 0);
+        assert_eq!(array.get(1), Some(Err(DecodeError::Version(2))));
+        assert!(array.get(3).is_none());
+        assert_eq!(array.slice(2, 3).sum().unwrap().decrypt(&key_pair), 2);
+        assert_eq!(array.sum(), Err(DecodeError::Version(2)));
+        assert!(matches!(
+            array.dot(&array),
+            Err(Error::Decode(DecodeError::Version(2)))
+        ));
+        assert!(matches!(
+            array.dot(&array.slice(0, 1)),
+            Err(Error::Length {
+                expected: 3,
+                found: 1
+            })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[quickcheck]
+    fn prop_compact_serde_roundtrip(x: u32) -> bool {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Payload {
+            #[serde(with = "compact")]
+            value: Enc,
+        }
+
+        let key_pair = KeyPair::new();
+        let payload = Payload {
+            value: Enc::encrypt(&key_pair, x),
+        };
+        let cbor = serde_cbor::to_vec(&payload).unwrap();
+        let json = serde_json::to_string(&payload).unwrap();
+        let from_cbor: Payload = serde_cbor::from_slice(&cbor).unwrap();
+        let from_json: Payload = serde_json::from_str(&json).unwrap();
+
+        from_cbor.value == payload.value
+            && from_json.value == payload.value
+            && payload.value.decrypt(&key_pair) == Mod231::from(x).0
+    }
+}
@@ -0,0 +1,43 @@
+//! A `NoiseStrategy` implemented outside the crate, through its public
+//! API only
+#![cfg(feature = "std")]
+
+use gmorph::algebra::{Matrix3, Mod231, Q231};
+use gmorph::noise::{NoiseStrategy, Triangular};
+use gmorph::*;
+use quickcheck_macros::quickcheck;
+use rand::Rng;
+
+/// Random first row over a random lower triangular 2x2 block
+struct LowerBlock;
+
+impl NoiseStrategy for LowerBlock {
+    fn embed<R: Rng + ?Sized>(&self, value: Q231, rng: &mut R) -> Matrix3<Q231> {
+        let mut noise = Matrix3::zeros();
+        noise[(0, 0)] = value;
+        for &(r, c) in &[(0, 1), (0, 2), (1, 1), (2, 1), (2, 2)] {
+            noise[(r, c)] = rng.gen();
+        }
+        noise
+    }
+}
+
+#[quickcheck]
+fn prop_lower_block_homomorphic(x: u32, y: u32) -> bool {
+    let key_pair = KeyPair::new();
+    let enc_x = Enc::encrypt_with_noise(&key_pair, x, &LowerBlock);
+    let enc_y = Enc::encrypt_with_noise(&key_pair, y, &LowerBlock);
+    let expected = Mod231::from(x) * Mod231::from(y) + Mod231::from(x);
+    let given: u32 = (enc_x * enc_y + enc_x).decrypt(&key_pair);
+    expected.value() == given
+}
+
+#[quickcheck]
+fn prop_lower_block_composes_with_triangular(x: u32, y: u32) -> bool {
+    let key_pair = KeyPair::new();
+    let enc_x = Enc::encrypt_with_noise(&key_pair, x, &LowerBlock);
+    let enc_y = Enc::encrypt_with_noise(&key_pair, y, &Triangular);
+    let expected = (Mod231::from(x) + Mod231::from(y)) * Mod231::from(y);
+    let given: u32 = ((enc_x + enc_y) * enc_y).decrypt(&key_pair);
+    expected.value() == given
+}